@@ -49,7 +49,7 @@ pub struct App {
     prev_time: Timespec,
     time_progression: TimeProgression,
     min_frame_ms: Option<f32>,
-    frame_streams: Vec<(ClientId, ChannelId)>
+    frame_streams: Vec<(ClientId, ChannelId, MessageEncoding)>
 }
 
 #[derive(PartialEq)]
@@ -67,13 +67,15 @@ pub struct AppOptions {
     pub viewport: pixelport_viewport::ViewportModuleOptions,
     pub port: u16,
     pub document: DocumentDescription,
+    pub load_mode: LoadMode,
     pub root_path: PathBuf,
     pub time_progression: TimeProgression,
     pub min_frame_ms: Option<f32>
 }
 
 impl App {
-    pub fn new(mut opts: AppOptions) -> App {
+    /// Fails if the document can't be loaded.
+    pub fn new(mut opts: AppOptions) -> Result<App, DocError> {
         let mut subdoc = pixelport_subdoc::SubdocModule::new();
         let mut template = pixelport_template::TemplateModule::new(opts.root_path.clone());
         let mut animation = pixelport_animation::AnimationModule::new();
@@ -122,6 +124,7 @@ impl App {
             }
 
         );
+        translater.register_to_pon::<FrameDescription>();
 
         let start_time = match &opts.time_progression {
             &TimeProgression::Real => time::get_time(),
@@ -137,17 +140,27 @@ impl App {
             arg: PonDocMatcher::Nil,
             category: "document".to_string(),
             module: "App".to_string(),
-            doc: "Get the current time".to_string()
+            doc: "Get the current time".to_string(),
+            pure: false
         });
 
         let mut document = match &opts.document {
             &DocumentDescription::Empty => Document::new_with_root(translater),
-            &DocumentDescription::FromFile(ref path) => Document::from_file(translater, path).unwrap()
+            &DocumentDescription::FromFile(ref path) => {
+                let (document, warnings) = try!(Document::from_file_with_mode(translater, path, opts.load_mode));
+                for warning in warnings {
+                    warn!("{}", warning);
+                }
+                document
+            }
         };
+        for error in document.validate() {
+            warn!("{}", error.to_string());
+        }
 
         viewport.set_doc(&mut document);
 
-        App {
+        Ok(App {
             document: document,
             document_channels: DocumentChannels::new(),
             subdoc: subdoc,
@@ -167,7 +180,7 @@ impl App {
             time_progression: opts.time_progression,
             min_frame_ms: opts.min_frame_ms,
             frame_streams: Vec::new()
-        }
+        })
     }
 
     pub fn update(&mut self) -> bool {
@@ -185,10 +198,11 @@ impl App {
         for outbound_message in self.document_channels.cycle_changes(&mut self.document, &cycle_changes) {
             self.tcpinterface.send_message(outbound_message);
         }
-        for &(ref client_id, ref channel_id) in &self.frame_streams {
+        for &(ref client_id, ref channel_id, encoding) in &self.frame_streams {
             self.tcpinterface.send_message(OutgoingMessage {
                 client_id: client_id.clone(),
                 channel_id: channel_id.clone(),
+                encoding: encoding,
                 message: Ok(Box::new(FrameDescription { dtime: dtime.num_milliseconds() as f32 / 1000.0 }))
             });
         }
@@ -240,11 +254,11 @@ impl App {
         if self.rendering.handle_request(&inc, &mut msgs, &mut self.document, &mut self.resources, &mut self.models) { return msgs; }
         if self.resources_channels.handle_request(&inc, &mut msgs, &mut self.document, &mut self.resources) { return msgs; }
         if let Some(frame_stream_create) = (*inc.message).downcast_ref::<FrameStreamCreateRequest>() {
-            self.frame_streams.push((inc.client_id.clone(), inc.channel_id.clone()));
+            self.frame_streams.push((inc.client_id.clone(), inc.channel_id.clone(), inc.encoding));
             return msgs;
         }
         if let Some(close_stream) = (*inc.message).downcast_ref::<CloseStreamRequest>() {
-            self.frame_streams.retain(|x| x != &(inc.client_id.clone(), close_stream.channel_id.to_string()));
+            self.frame_streams.retain(|&(ref client_id, ref channel_id, _)| !(client_id == &inc.client_id && channel_id == &close_stream.channel_id));
             return vec![inc.ok(())];
         }
         return vec![inc.bad_request(&format!("No handler registered for message {:?}", inc.message))];
@@ -252,7 +266,7 @@ impl App {
     pub fn remove_client(&mut self, client_id: &ClientId) {
         self.document_channels.remove_client(&client_id);
         self.viewport.remove_client(&client_id);
-        self.frame_streams.retain(|&(ref cid, _)| cid != client_id);
+        self.frame_streams.retain(|&(ref cid, _, _)| cid != client_id);
     }
 }
 
@@ -289,10 +303,11 @@ pub extern "C" fn pixelport_new() -> *mut CApp {
             },
             port: 4303,
             document: DocumentDescription::Empty,
+            load_mode: LoadMode::Lenient,
             root_path: Path::new(".").to_path_buf(),
             time_progression: TimeProgression::Real,
             min_frame_ms: None
-        }),
+        }).expect("Failed to create an app with an empty document"),
         request_counter: 0
     });
     unsafe { mem::transmute(app) }
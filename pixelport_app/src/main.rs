@@ -34,7 +34,9 @@ Options:
   --height=<px>            Window height.
   --fixedtimestep=<ms>     Fix the frame time step to x ms.
   --maxfps=<ms>            Max fps [default: 600].
+  --strict                 Fail on any warning while loading the document.
   --genpondocs             Output Pon documentation to stdout and exit.
+  --parsepon=<pon>         Parse a Pon expression, print it normalized (or the parse error) and exit.
 ";
 
 #[derive(Debug, RustcDecodable)]
@@ -49,7 +51,9 @@ pub struct Args {
     flag_height: Option<u32>,
     flag_fixedtimestep: Option<u32>,
     flag_maxfps: Option<f32>,
+    flag_strict: bool,
     flag_genpondocs: bool,
+    flag_parsepon: Option<String>,
 }
 
 fn main() {
@@ -58,6 +62,17 @@ fn main() {
                             .and_then(|d| d.decode())
                             .unwrap_or_else(|e| e.exit());
 
+    if let Some(ref source) = args.flag_parsepon {
+        match Pon::from_string(source) {
+            Ok(pon) => println!("{}", pon.to_string()),
+            Err(diagnostic) => {
+                write!(std::io::stderr(), "{}", diagnostic.render()).unwrap();
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
     let (doc, root_path) = {
         if let Some(filename) = args.arg_document {
             let path = Path::new(&filename);
@@ -68,7 +83,7 @@ fn main() {
         }
     };
 
-    let mut app = App::new(AppOptions {
+    let mut app = match App::new(AppOptions {
         viewport: pixelport_viewport::ViewportModuleOptions {
             fullscreen: args.flag_fullscreen,
             multisampling: args.flag_multisampling,
@@ -82,6 +97,7 @@ fn main() {
         },
         port: args.flag_port,
         document: doc,
+        load_mode: if args.flag_strict { LoadMode::Strict } else { LoadMode::Lenient },
         root_path: root_path,
         time_progression: match args.flag_fixedtimestep {
             Some(v) => TimeProgression::Fixed { step_ms: v },
@@ -91,7 +107,13 @@ fn main() {
             Some(v) => Some(1000.0 / v),
             None => None
         }
-    });
+    }) {
+        Ok(app) => app,
+        Err(err) => {
+            error!("{}", err.to_string());
+            std::process::exit(1);
+        }
+    };
 
     if args.flag_genpondocs {
         println!("{}", app.document.translater.generate_json_docs());
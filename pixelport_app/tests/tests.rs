@@ -6,6 +6,7 @@ extern crate image;
 extern crate glutin;
 
 use pixelport::*;
+use pixelport_document::LoadMode;
 
 use std::path::Path;
 use pixelport_resources::*;
@@ -24,6 +25,7 @@ fn headless_document_opts(filename: &str) -> AppOptions {
         },
         port: 0,
         document: DocumentDescription::FromFile(path.to_path_buf()),
+        load_mode: LoadMode::Strict,
         root_path: root_path,
         time_progression: TimeProgression::Fixed { step_ms: 16 },
         min_frame_ms: None
@@ -31,7 +33,7 @@ fn headless_document_opts(filename: &str) -> AppOptions {
 }
 
 fn setup_app(name: &str) -> App {
-	let mut app = App::new(headless_document_opts(&format!("../examples/{}.pml", name)));
+	let mut app = App::new(headless_document_opts(&format!("../examples/{}.pml", name))).unwrap();
 	app.update();
     app.resources.await_all();
     app.update();
@@ -0,0 +1,45 @@
+#![feature(test)]
+
+extern crate test;
+extern crate pixelport_document;
+
+use test::Bencher;
+use pixelport_document::*;
+
+// A root with 100 groups of 1000 entities, each entity with two properties
+fn build_doc() -> (Document, EntityId) {
+    let mut doc = Document::new_with_root(PonTranslater::new());
+    let root = doc.get_root().unwrap();
+    let subtree = doc.append_entity(None, Some(root), "Entity", None).unwrap();
+    for _ in 0..100 {
+        let group = doc.append_entity(None, Some(subtree), "Entity", None).unwrap();
+        for i in 0..1000 {
+            let entity = doc.append_entity(None, Some(group), "Entity", None).unwrap();
+            doc.set_property(entity, "x", Pon::Number(i as f32), false).unwrap();
+            doc.set_property(entity, "y", Pon::from_string("@this.x").unwrap(), false).unwrap();
+        }
+    }
+    (doc, subtree)
+}
+
+#[bench]
+fn bench_build_100k(b: &mut Bencher) {
+    b.iter(|| build_doc());
+}
+
+// Subtract bench_build_100k to get the time of the removal
+#[bench]
+fn bench_build_and_remove_subtree_100k(b: &mut Bencher) {
+    b.iter(|| {
+        let (mut doc, subtree) = build_doc();
+        doc.remove_entity(subtree).unwrap();
+        doc
+    });
+}
+
+#[bench]
+fn bench_get_properties_100k(b: &mut Bencher) {
+    let (doc, _) = build_doc();
+    let entity = doc.entities_iter().max().cloned().unwrap();
+    b.iter(|| doc.get_properties(entity).unwrap());
+}
@@ -1,18 +1,22 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::collections::hash_map::Entry;
 use std::mem;
+use std::cmp;
+use std::any::TypeId;
 use std::marker::Reflect;
 use mopa;
 use inverse_dependencies_counter::*;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use pon::*;
 use pon_translater::*;
+use document::EntityId;
 
 use std::fmt::Debug;
 pub trait BusValue: mopa::Any + Debug + Send {
     fn bus_value_equals(&self, other: &Box<BusValue>) -> bool;
     fn bus_value_clone(&self) -> Box<BusValue>;
     fn bus_value_type_name(&self) -> &str;
+    fn bus_value_type_id(&self) -> TypeId;
 }
 mopafy!(BusValue);
 
@@ -31,6 +35,9 @@ impl<T: PartialEq + Reflect + 'static + Debug + Clone + Send> BusValue for T {
             ::std::intrinsics::type_name::<T>()
         }
     }
+    fn bus_value_type_id(&self) -> TypeId {
+        TypeId::of::<T>()
+    }
 }
 
 impl Clone for Box<BusValue> {
@@ -49,8 +56,7 @@ pub type ValueConstructor = Fn(&Bus, &PonTranslater) -> Result<Box<BusValue>, Bu
 pub enum BusEntryValue {
     Constructor {
         constructor: Box<ValueConstructor>,
-        cached: RefCell<Option<Box<BusValue>>>,
-        cached_until: RefCell<u64>
+        cached: RefCell<Option<Box<BusValue>>>
     },
     Value(Box<BusValue>),
 
@@ -58,11 +64,18 @@ pub enum BusEntryValue {
     // things with the entries, such as when setting the same value twice it shouldn't do anything.
     Pon {
         expression: Pon,
-        cached: RefCell<Option<Box<BusValue>>>,
-        cached_until: RefCell<u64>
+        cached: RefCell<Option<Box<BusValue>>>
     }
 }
 impl BusEntryValue {
+    // True if there was a cached value
+    fn clear_cache(&self) -> bool {
+        match self {
+            &BusEntryValue::Constructor { ref cached, .. } |
+            &BusEntryValue::Pon { ref cached, .. } => cached.borrow_mut().take().is_some(),
+            &BusEntryValue::Value(_) => false
+        }
+    }
     fn is_same(&self, other: &BusEntryValue) -> bool {
         match other {
             &BusEntryValue::Constructor { .. } => false,
@@ -80,10 +93,12 @@ impl BusEntryValue {
 
 struct BusEntry {
     value: BusEntryValue,
-    volatile: bool
+    volatile: bool,
+    // The last walk over dependents that reached this entry, see `clear_dependents_cache`
+    walk_id: Cell<u64>
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct InvalidatedChange {
     pub added: Vec<PropRef>,
     pub removed: Vec<PropRef>,
@@ -102,6 +117,7 @@ pub struct BusStats {
     pub n_set_constructor: i32,
     pub n_set_pon: i32,
     pub n_skip_set: i32,
+    pub n_cache_invalidations: i32,
 }
 impl BusStats {
     pub fn new() -> BusStats {
@@ -117,15 +133,34 @@ impl BusStats {
             n_set_constructor: 0,
             n_set_pon: 0,
             n_skip_set: 0,
+            n_cache_invalidations: 0,
         }
     }
 }
 
+// The state of the bus when a transaction was started. Entries are saved the first time they're
+// changed, None meaning the entry didn't exist. The dependencies are saved the same way by
+// `InverseDependenciesCounter`. The invalidations log is only appended to, unless it's taken.
+struct BusTransaction {
+    entries: HashMap<PropRef, Option<BusEntry>>,
+    invalidations_log_len: usize,
+    taken_invalidations_log: Option<Vec<InvalidatedChange>>,
+    errors: HashMap<PropRef, BusError>,
+    reported_errors: HashSet<PropRef>
+}
+
 pub struct Bus {
     entries: HashMap<PropRef, BusEntry>,
+    // The keys of `entries`, by entity
+    entity_entries: HashMap<EntityId, HashSet<PropRef>>,
     pub invalidations_log: Vec<InvalidatedChange>,
     inv_dep_counter: InverseDependenciesCounter<PropRef>,
-    cycle: u64,
+    transaction: Option<BusTransaction>,
+    // The error of the last evaluation of each entry, if it failed
+    errors: RefCell<HashMap<PropRef, BusError>>,
+    // The entries that were failing when `update_errors` was last called
+    reported_errors: HashSet<PropRef>,
+    walk_counter: Cell<u64>,
     pub stats: RefCell<BusStats>
 }
 
@@ -134,6 +169,7 @@ pub enum BusError {
     NoSuchEntry { prop_ref: PropRef },
     EntryOfWrongType { expected: String, found: String, value: String },
     PonTranslateError { err: PonTranslaterErr },
+    DependencyCycle { path: Vec<PropRef> },
     Generic(String)
 }
 impl From<PonTranslaterErr> for BusError {
@@ -146,6 +182,10 @@ impl ToString for BusError {
         match self {
             &BusError::PonTranslateError { ref err } =>
                 format!("Pon translate error: {}", err.to_string()),
+            &BusError::DependencyCycle { ref path } => {
+                let path: Vec<String> = path.iter().map(|p| format!("{}.{}", p.entity_id, p.property_key)).collect();
+                format!("Dependency cycle: {}", path.join(" -> "))
+            },
             _ => format!("{:?}", self)
         }
     }
@@ -156,58 +196,104 @@ impl Bus {
     pub fn new() -> Bus {
         Bus {
             entries: HashMap::new(),
+            entity_entries: HashMap::new(),
             invalidations_log: Vec::new(),
             inv_dep_counter: InverseDependenciesCounter::new(),
-            cycle: 1,
+            transaction: None,
+            errors: RefCell::new(HashMap::new()),
+            reported_errors: HashSet::new(),
+            walk_counter: Cell::new(0),
             stats: RefCell::new(BusStats::new())
         }
     }
     pub fn set_value(&mut self, key: &PropRef, volatile: bool, value: Box<BusValue>) {
         self.stats.borrow_mut().n_set_value += 1;
-        self.set(key, Vec::new(), volatile, BusEntryValue::Value(value));
+        // Without dependencies there can't be a cycle
+        self.set(key, Vec::new(), volatile, BusEntryValue::Value(value)).unwrap();
     }
-    pub fn set_constructor(&mut self, key: &PropRef, dependencies: Vec<PropRef>, volatile: bool, construct: Box<ValueConstructor>) {
+    /// The constructed value is cached until one of the `dependencies` is set, so the
+    /// constructor should only read those. Constructors that read anything else, or that should
+    /// run again each cycle, must be volatile.
+    ///
+    /// Sets that would make the entry depend on itself are rejected with a `DependencyCycle`
+    /// error, leaving the entry as it was.
+    pub fn set_constructor(&mut self, key: &PropRef, dependencies: Vec<PropRef>, volatile: bool, construct: Box<ValueConstructor>) -> Result<(), BusError> {
         self.stats.borrow_mut().n_set_constructor += 1;
         self.set(key, dependencies, volatile, BusEntryValue::Constructor {
             constructor: construct,
             cached: RefCell::new(None),
-            cached_until: RefCell::new(0),
-        });
+        })
     }
-    pub fn set_pon(&mut self, key: &PropRef, volatile: bool, expression: Pon) {
+    /// Expressions that call impure functions are always set as volatile. Like
+    /// `set_constructor`, sets that would create a dependency cycle are rejected.
+    pub fn set_pon(&mut self, key: &PropRef, volatile: bool, expression: Pon, pon_translater: &PonTranslater) -> Result<(), BusError> {
         let mut dependencies = vec![];
         expression.build_dependencies_array(&mut dependencies);
+        self.set_pon_with_dependencies(key, volatile, expression, dependencies, pon_translater)
+    }
+    /// Like `set_pon`, but with dependencies given by the caller instead of taken from the
+    /// expression. The dependencies should include the ones in the expression.
+    pub fn set_pon_with_dependencies(&mut self, key: &PropRef, volatile: bool, expression: Pon, dependencies: Vec<PropRef>, pon_translater: &PonTranslater) -> Result<(), BusError> {
         self.stats.borrow_mut().n_set_pon += 1;
+        let volatile = volatile || !pon_translater.is_pure(&expression);
         self.set(key, dependencies, volatile, BusEntryValue::Pon {
             expression: expression,
             cached: RefCell::new(None),
-            cached_until: RefCell::new(0),
-        });
+        })
     }
-    fn set(&mut self, key: &PropRef, dependencies: Vec<PropRef>, volatile: bool, value: BusEntryValue) {
-        let mut change = ChangedNonZero::new();
-        let was_volatile = {
+    fn set(&mut self, key: &PropRef, dependencies: Vec<PropRef>, volatile: bool, value: BusEntryValue) -> Result<(), BusError> {
+        if let Some(path) = self.inv_dep_counter.find_cycle(key, &dependencies) {
+            return Err(BusError::DependencyCycle { path: path });
+        }
+        let (was_volatile, old_entry) = {
             match self.entries.entry(key.clone()) {
                 Entry::Occupied(o) => {
                     let mut e = o.into_mut();
                     // Early exit, means we can save the whole inv added removed thing for non-volatile sets
                     if !volatile && e.value.is_same(&value) {
                         self.stats.borrow_mut().n_skip_set += 1;
-                        return;
+                        return Ok(());
                     }
-                    e.value = value;
-                    mem::replace(&mut e.volatile, volatile)
+                    let old_value = mem::replace(&mut e.value, value);
+                    let was_volatile = mem::replace(&mut e.volatile, volatile);
+                    (was_volatile, Some(BusEntry { value: old_value, volatile: was_volatile, walk_id: Cell::new(0) }))
                 },
                 Entry::Vacant(v) => {
                     v.insert(BusEntry {
                         value: value,
-                        volatile: volatile
+                        volatile: volatile,
+                        walk_id: Cell::new(0)
                     });
-                    false
+                    (false, None)
                 }
             }
         };
+        if old_entry.is_none() {
+            self.index_entry(key);
+        }
+        self.save_entry(key, old_entry);
 
+        self.update_dependencies(key, dependencies, volatile, was_volatile);
+        self.clear_dependents_cache(key);
+        Ok(())
+    }
+    /// Replaces the dependencies of an entry without changing its value. The entry and its
+    /// dependents are invalidated as if it was set.
+    pub fn set_dependencies(&mut self, key: &PropRef, dependencies: Vec<PropRef>) -> Result<(), BusError> {
+        let volatile = match self.entries.get(key) {
+            Some(entry) => entry.volatile,
+            None => return Ok(())
+        };
+        if let Some(path) = self.inv_dep_counter.find_cycle(key, &dependencies) {
+            return Err(BusError::DependencyCycle { path: path });
+        }
+        self.update_dependencies(key, dependencies, volatile, volatile);
+        self.clear_entry_cache(key);
+        self.clear_dependents_cache(key);
+        Ok(())
+    }
+    fn update_dependencies(&mut self, key: &PropRef, dependencies: Vec<PropRef>, volatile: bool, was_volatile: bool) {
+        let mut change = ChangedNonZero::new();
         if volatile {
             self.stats.borrow_mut().n_volatile_sets += 1;
             if !was_volatile {
@@ -238,31 +324,31 @@ impl Bus {
         match self.entries.get(key) {
             Some(entry) => {
                 match &entry.value {
-                    &BusEntryValue::Constructor { ref cached, ref cached_until, ref constructor,  } => {
-                        if *cached_until.borrow() >= self.cycle {
+                    &BusEntryValue::Constructor { ref cached, ref constructor,  } => {
+                        if let &Some(ref v) = &*cached.borrow() {
                             self.stats.borrow_mut().n_cache_hits += 1;
-                            if let &Some(ref v) = &*cached.borrow() {
-                                return Ok((**v).bus_value_clone());
-                            }
+                            return Ok((**v).bus_value_clone());
                         }
                         self.stats.borrow_mut().n_constructs += 1;
-                        let v = try!((*constructor)(self, pon_translater));
+                        let v = try!(self.record_evaluation(key, (*constructor)(self, pon_translater)));
                         *cached.borrow_mut() = Some((*v).bus_value_clone());
-                        *cached_until.borrow_mut() = self.cycle;
                         Ok(v)
                     },
                     &BusEntryValue::Value(ref value) => Ok((**value).bus_value_clone()),
-                    &BusEntryValue::Pon { ref cached, ref cached_until, ref expression,  } => {
-                        if *cached_until.borrow() >= self.cycle {
+                    &BusEntryValue::Pon { ref cached, ref expression,  } => {
+                        if let &Some(ref v) = &*cached.borrow() {
                             self.stats.borrow_mut().n_cache_hits += 1;
-                            if let &Some(ref v) = &*cached.borrow() {
-                                return Ok((**v).bus_value_clone());
-                            }
+                            return Ok((**v).bus_value_clone());
                         }
                         self.stats.borrow_mut().n_constructs += 1;
-                        let v = try!(pon_translater.translate_raw(expression, self));
+                        // The value of a function body is the expression itself, it's only
+                        // translated when the function is called
+                        let v = try!(self.record_evaluation(key, if pon_translater.is_document_function_body(key) {
+                            Ok(Box::new(expression.clone()) as Box<BusValue>)
+                        } else {
+                            pon_translater.translate_raw(expression, self).map_err(BusError::from)
+                        }));
                         *cached.borrow_mut() = Some((*v).bus_value_clone());
-                        *cached_until.borrow_mut() = self.cycle;
                         Ok(v)
                     },
                 }
@@ -270,9 +356,54 @@ impl Bus {
             None => Err(BusError::NoSuchEntry { prop_ref: key.clone() })
         }
     }
+    fn record_evaluation(&self, key: &PropRef, result: Result<Box<BusValue>, BusError>) -> Result<Box<BusValue>, BusError> {
+        match &result {
+            &Ok(_) => { self.errors.borrow_mut().remove(key); },
+            &Err(ref err) => { self.errors.borrow_mut().insert(key.clone(), err.clone()); }
+        }
+        result
+    }
+    /// The error of the last evaluation of `key`, if it failed.
+    pub fn get_error(&self, key: &PropRef) -> Option<BusError> {
+        self.errors.borrow().get(key).cloned()
+    }
+    /// All entries whose last evaluation failed, sorted.
+    pub fn get_errors(&self) -> Vec<(PropRef, BusError)> {
+        let mut errors: Vec<(PropRef, BusError)> = self.errors.borrow().iter()
+            .map(|(prop_ref, err)| (prop_ref.clone(), err.clone())).collect();
+        errors.sort_by(|a, b| a.0.cmp(&b.0));
+        errors
+    }
+    /// Returns the entries that started and stopped failing since the last call. Errors are
+    /// recorded when entries are evaluated, so this only evaluates the failing entries and the
+    /// ones set or invalidated since the invalidations log was last taken. Volatile entries and
+    /// their dependents are left to be found when they're read, since they're evaluated again
+    /// each cycle anyway.
+    pub fn update_errors(&mut self, pon_translater: &PonTranslater) -> (Vec<PropRef>, Vec<PropRef>) {
+        let mut check: HashSet<PropRef> = self.errors.borrow().keys().cloned().collect();
+        for change in &self.invalidations_log {
+            check.extend(change.added.iter().cloned());
+        }
+        for prop_ref in &check {
+            if !self.has(prop_ref) {
+                self.errors.borrow_mut().remove(prop_ref);
+            } else if !self.inv_dep_counter.is_nonzero(prop_ref) {
+                self.get(prop_ref, pon_translater).ok();
+            }
+        }
+        let failing: HashSet<PropRef> = self.errors.borrow().keys().cloned().collect();
+        let mut started: Vec<PropRef> = failing.difference(&self.reported_errors).cloned().collect();
+        let mut stopped: Vec<PropRef> = self.reported_errors.difference(&failing).cloned().collect();
+        started.sort();
+        stopped.sort();
+        self.reported_errors = failing;
+        (started, stopped)
+    }
+    /// Values are converted like in `PonTranslater::translate`, so that `x="5"` can be read as an
+    /// f32 and registered coercions apply.
     pub fn get_typed<T: BusValue>(&self, key: &PropRef, pon_translater: &PonTranslater) -> Result<T, BusError> {
-        match try!(self.get(key, pon_translater)).downcast::<T>() {
-            Ok(box v) => Ok(v),
+        match pon_translater.convert::<T>(try!(self.get(key, pon_translater))) {
+            Ok(v) => Ok(v),
             Err(v) => {
                 let expected_type_name = unsafe {
                     ::std::intrinsics::type_name::<T>()
@@ -281,6 +412,19 @@ impl Bus {
             }
         }
     }
+    pub fn reset_stats(&mut self) {
+        self.stats = RefCell::new(BusStats::new());
+    }
+    /// The value of `key` if it's known without evaluating it, which is the case for native
+    /// values and cached values.
+    pub fn get_cached(&self, key: &PropRef) -> Option<Box<BusValue>> {
+        match self.entries.get(key) {
+            Some(&BusEntry { value: BusEntryValue::Value(ref value), .. }) => Some(value.clone()),
+            Some(&BusEntry { value: BusEntryValue::Constructor { ref cached, .. }, .. }) |
+            Some(&BusEntry { value: BusEntryValue::Pon { ref cached, .. }, .. }) => cached.borrow().clone(),
+            None => None
+        }
+    }
     pub fn get_entry(&self, key: &PropRef) -> Option<&BusEntryValue> {
         match self.entries.get(key) {
             Some(v) => Some(&v.value),
@@ -288,8 +432,99 @@ impl Bus {
         }
     }
     pub fn remove(&mut self, key: &PropRef) {
+        self.clear_dependents_cache(key);
         self.inv_dep_counter.remove_property(key);
-        self.entries.remove(key);
+        let old_entry = self.entries.remove(key);
+        if old_entry.is_some() {
+            self.unindex_entry(key);
+        }
+        self.save_entry(key, old_entry);
+    }
+    /// Starts recording changes, so that they can be undone with `rollback_transaction`.
+    /// Transactions can't be nested; starting one while another is in progress does nothing.
+    pub fn begin_transaction(&mut self) {
+        if self.transaction.is_none() {
+            self.transaction = Some(BusTransaction {
+                entries: HashMap::new(),
+                invalidations_log_len: self.invalidations_log.len(),
+                taken_invalidations_log: None,
+                errors: self.errors.borrow().clone(),
+                reported_errors: self.reported_errors.clone()
+            });
+            self.inv_dep_counter.begin_transaction();
+        }
+    }
+    pub fn commit_transaction(&mut self) {
+        self.transaction = None;
+        self.inv_dep_counter.commit_transaction();
+    }
+    /// Restores the entries, dependencies, invalidations log and errors to what they were when
+    /// the transaction was started.
+    pub fn rollback_transaction(&mut self) {
+        let transaction = match self.transaction.take() {
+            Some(transaction) => transaction,
+            None => return
+        };
+        for (key, entry) in transaction.entries {
+            match entry {
+                Some(entry) => {
+                    self.index_entry(&key);
+                    self.entries.insert(key, entry);
+                },
+                None => {
+                    self.unindex_entry(&key);
+                    self.entries.remove(&key);
+                }
+            }
+        }
+        self.inv_dep_counter.rollback_transaction();
+        match transaction.taken_invalidations_log {
+            Some(log) => self.invalidations_log = log,
+            None => self.invalidations_log.truncate(transaction.invalidations_log_len)
+        }
+        *self.errors.borrow_mut() = transaction.errors;
+        self.reported_errors = transaction.reported_errors;
+        // Values cached during the transaction may have been evaluated from entries that are now
+        // restored, or from function definitions that no longer exist
+        for entry in self.entries.values() {
+            entry.value.clear_cache();
+        }
+    }
+    pub fn in_transaction(&self) -> bool {
+        self.transaction.is_some()
+    }
+    /// Takes the invalidations log, leaving it empty. During a transaction this keeps the part of
+    /// the log from before the transaction, so that a rollback can restore it.
+    pub fn take_invalidations_log(&mut self) -> Vec<InvalidatedChange> {
+        if let Some(ref mut transaction) = self.transaction {
+            if transaction.taken_invalidations_log.is_none() {
+                let len = cmp::min(transaction.invalidations_log_len, self.invalidations_log.len());
+                transaction.taken_invalidations_log = Some(self.invalidations_log[..len].to_vec());
+            }
+        }
+        mem::replace(&mut self.invalidations_log, Vec::new())
+    }
+    fn save_entry(&mut self, key: &PropRef, entry: Option<BusEntry>) {
+        if let Some(ref mut transaction) = self.transaction {
+            if !transaction.entries.contains_key(key) {
+                transaction.entries.insert(key.clone(), entry);
+            }
+        }
+    }
+    fn index_entry(&mut self, key: &PropRef) {
+        self.entity_entries.entry(key.entity_id).or_insert(HashSet::new()).insert(key.clone());
+    }
+    fn unindex_entry(&mut self, key: &PropRef) {
+        let is_empty = match self.entity_entries.get_mut(&key.entity_id) {
+            Some(keys) => {
+                keys.remove(key);
+                keys.is_empty()
+            },
+            None => false
+        };
+        if is_empty {
+            self.entity_entries.remove(&key.entity_id);
+        }
     }
     pub fn has(&self, key: &PropRef) -> bool {
         self.entries.contains_key(key)
@@ -297,11 +532,87 @@ impl Bus {
     pub fn iter<'a>(&'a self) -> Box<Iterator<Item=&'a PropRef> + 'a> {
         Box::new(self.entries.keys())
     }
+    /// Iterates over the entries of one entity, without going through all entries.
+    pub fn iter_entity<'a>(&'a self, entity_id: EntityId) -> Box<Iterator<Item=&'a PropRef> + 'a> {
+        match self.entity_entries.get(&entity_id) {
+            Some(keys) => Box::new(keys.iter()),
+            None => Box::new(::std::iter::empty::<&'a PropRef>())
+        }
+    }
     pub fn iter_invalidated<'a>(&'a self) -> Box<Iterator<Item=&'a PropRef> + 'a> {
         Box::new(self.inv_dep_counter.iter_nonzero())
     }
+    /// The properties `key` depends on, sorted. With `transitive` also the properties those depend
+    /// on, and so on. Dependencies that aren't set yet are included.
+    pub fn get_dependencies(&self, key: &PropRef, transitive: bool) -> Vec<PropRef> {
+        self.collect_related(key, transitive, |k| self.inv_dep_counter.get_dependencies(k))
+    }
+    /// The properties that depend on `key`, sorted. With `transitive` also their dependents, and
+    /// so on.
+    pub fn get_dependents(&self, key: &PropRef, transitive: bool) -> Vec<PropRef> {
+        self.collect_related(key, transitive, |k| self.inv_dep_counter.get_dependents(k))
+    }
+    /// If making `key` depend on `dependencies` would create a cycle, returns the cycle as a path
+    /// that starts and ends with `key`.
+    pub fn find_cycle(&self, key: &PropRef, dependencies: &[PropRef]) -> Option<Vec<PropRef>> {
+        self.inv_dep_counter.find_cycle(key, dependencies)
+    }
+    /// How many volatile or changed properties `key` is currently invalidated by, itself included.
+    pub fn get_invalidation_counter(&self, key: &PropRef) -> i32 {
+        self.inv_dep_counter.get_counter(key)
+    }
+    pub fn is_volatile(&self, key: &PropRef) -> bool {
+        match self.entries.get(key) {
+            Some(entry) => entry.volatile,
+            None => false
+        }
+    }
+    fn collect_related<F: Fn(&PropRef) -> Vec<PropRef>>(&self, key: &PropRef, transitive: bool, related: F) -> Vec<PropRef> {
+        let mut found = HashSet::new();
+        let mut queue = related(key);
+        while let Some(prop_ref) = queue.pop() {
+            if found.insert(prop_ref.clone()) && transitive {
+                queue.extend(related(&prop_ref));
+            }
+        }
+        let mut found: Vec<PropRef> = found.into_iter().collect();
+        found.sort();
+        found
+    }
+    /// Ends the cycle. Evaluated values stay cached until the entry or one of its dependencies is
+    /// set, except for volatile entries and their dependents, which are evaluated again each cycle.
     pub fn clear_cache(&mut self) {
-        self.cycle += 1;
-        self.stats = RefCell::new(BusStats::new());
+        let volatile: Vec<PropRef> = self.inv_dep_counter.iter_nonzero().cloned().collect();
+        for key in &volatile {
+            self.clear_entry_cache(key);
+        }
+    }
+    fn clear_entry_cache(&self, key: &PropRef) {
+        if let Some(entry) = self.entries.get(key) {
+            if entry.value.clear_cache() {
+                self.stats.borrow_mut().n_cache_invalidations += 1;
+            }
+        }
+    }
+    // This runs on every set, so the dependents are walked without collecting them. Entries
+    // reached through several paths are only visited once, by marking them with the id of the walk.
+    fn clear_dependents_cache(&self, key: &PropRef) {
+        let walk_id = self.walk_counter.get() + 1;
+        self.walk_counter.set(walk_id);
+        self.clear_dependents_cache_walk(key, walk_id);
+    }
+    fn clear_dependents_cache_walk(&self, key: &PropRef, walk_id: u64) {
+        for dependent in self.inv_dep_counter.dependents(key) {
+            if let Some(entry) = self.entries.get(dependent) {
+                if entry.walk_id.get() == walk_id {
+                    continue;
+                }
+                entry.walk_id.set(walk_id);
+                if entry.value.clear_cache() {
+                    self.stats.borrow_mut().n_cache_invalidations += 1;
+                }
+                self.clear_dependents_cache_walk(dependent, walk_id);
+            }
+        }
     }
 }
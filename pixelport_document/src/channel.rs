@@ -13,23 +13,20 @@ pub struct RequestError {
     pub error_type: RequestErrorType,
     pub message: String
 }
-impl ToPon for RequestError {
-    fn to_pon(&self) -> Pon {
-        Pon::call("request_error", Pon::Object(hashmap![
-            "error_type" => match self.error_type {
-                RequestErrorType::BadRequest => "bad_request".to_pon(),
-                RequestErrorType::InternalError => "internal_error".to_pon(),
-            },
-            "message" => self.message.to_pon()
-        ]))
-    }
-}
+pon_struct!(RequestError "request_error" {
+    error_type: (RequestErrorType),
+    message: (String),
+});
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum RequestErrorType {
     BadRequest,
     InternalError,
 }
+pon_enum!(RequestErrorType {
+    BadRequest => "bad_request",
+    InternalError => "internal_error",
+});
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum ClientId {
@@ -41,19 +38,29 @@ pub enum ClientId {
 // This is either a request_id or a stream_id
 pub type ChannelId = String;
 
+// How a message is written on the wire. Responses use the encoding of the request they answer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MessageEncoding {
+    Pon,
+    Json
+}
+
 #[derive(Debug)]
 pub struct IncomingMessage {
     pub client_id: ClientId,
     pub channel_id: ChannelId,
+    pub encoding: MessageEncoding,
     pub message: Box<BusValue>
 }
 impl IncomingMessage {
+    // Messages are either Pon or Json; a Json message is always an object, which is never a valid Pon request.
     pub fn from_tcpstring(translater: &PonTranslater, bus: &mut Bus, client_id: ClientId, message: &str) -> Result<IncomingMessage, OutgoingMessage> {
         let split: Vec<&str> = message.splitn(2, " ").collect();
         if split.len() != 2 {
             return Err(OutgoingMessage {
                 channel_id: "unknown".to_string(),
                 client_id: client_id,
+                encoding: MessageEncoding::Pon,
                 message: Err(RequestError {
                     error_type: RequestErrorType::BadRequest,
                     message: "Expected format: <channel_id> <message>".to_string()
@@ -61,28 +68,33 @@ impl IncomingMessage {
             });
         }
         let channel_id = split[0].to_string();
-        IncomingMessage::from_string(translater, bus, client_id, channel_id, &split[1])
+        if split[1].trim_left().starts_with("{") {
+            IncomingMessage::from_json_string(translater, bus, client_id, channel_id, &split[1])
+        } else {
+            IncomingMessage::from_string(translater, bus, client_id, channel_id, &split[1])
+        }
     }
     pub fn from_string(translater: &PonTranslater, bus: &mut Bus, client_id: ClientId, channel_id: ChannelId, message: &str) -> Result<IncomingMessage, OutgoingMessage> {
         match Pon::from_string(message) {
-            Ok(pon) => match translater.translate_raw(&pon, bus) {
-                Ok(message) => Ok(IncomingMessage {
-                    channel_id: channel_id,
-                    client_id: client_id,
-                    message: message
-                }),
-                Err(err) => Err(OutgoingMessage {
-                    channel_id: channel_id,
-                    client_id: client_id,
-                    message: Err(RequestError {
-                        error_type: RequestErrorType::BadRequest,
-                        message: format!("Unable to translate request: {}", err.to_string())
-                    })
+            Ok(pon) => IncomingMessage::from_pon(translater, bus, client_id, channel_id, MessageEncoding::Pon, &pon),
+            Err(diagnostic) => Err(OutgoingMessage {
+                channel_id: channel_id,
+                client_id: client_id,
+                encoding: MessageEncoding::Pon,
+                message: Err(RequestError {
+                    error_type: RequestErrorType::BadRequest,
+                    message: format!("Unable to parse request:\n{}", diagnostic.render())
                 })
-            },
+            })
+        }
+    }
+    pub fn from_json_string(translater: &PonTranslater, bus: &mut Bus, client_id: ClientId, channel_id: ChannelId, message: &str) -> Result<IncomingMessage, OutgoingMessage> {
+        match Pon::from_json_string(message) {
+            Ok(pon) => IncomingMessage::from_pon(translater, bus, client_id, channel_id, MessageEncoding::Json, &pon),
             Err(err) => Err(OutgoingMessage {
                 channel_id: channel_id,
                 client_id: client_id,
+                encoding: MessageEncoding::Json,
                 message: Err(RequestError {
                     error_type: RequestErrorType::BadRequest,
                     message: format!("Unable to parse request: {}", err.to_string())
@@ -90,10 +102,30 @@ impl IncomingMessage {
             })
         }
     }
+    fn from_pon(translater: &PonTranslater, bus: &mut Bus, client_id: ClientId, channel_id: ChannelId, encoding: MessageEncoding, pon: &Pon) -> Result<IncomingMessage, OutgoingMessage> {
+        match translater.translate_raw(pon, bus) {
+            Ok(message) => Ok(IncomingMessage {
+                channel_id: channel_id,
+                client_id: client_id,
+                encoding: encoding,
+                message: message
+            }),
+            Err(err) => Err(OutgoingMessage {
+                channel_id: channel_id,
+                client_id: client_id,
+                encoding: encoding,
+                message: Err(RequestError {
+                    error_type: RequestErrorType::BadRequest,
+                    message: format!("Unable to translate request: {}", err.to_string())
+                })
+            })
+        }
+    }
     pub fn ok<T: OutMessage + 'static>(&self, response: T) -> OutgoingMessage {
         OutgoingMessage {
             channel_id: self.channel_id.to_string(),
             client_id: self.client_id.clone(),
+            encoding: self.encoding,
             message: Ok(Box::new(response))
         }
     }
@@ -101,6 +133,7 @@ impl IncomingMessage {
         OutgoingMessage {
             channel_id: self.channel_id.to_string(),
             client_id: self.client_id.clone(),
+            encoding: self.encoding,
             message: Err(RequestError {
                 error_type: error_type,
                 message: message.to_string()
@@ -119,13 +152,18 @@ impl IncomingMessage {
 pub struct OutgoingMessage {
     pub channel_id: ChannelId,
     pub client_id: ClientId,
+    pub encoding: MessageEncoding,
     pub message: Result<Box<OutMessage>, RequestError>
 }
 impl OutgoingMessage {
     pub fn to_tcpmessage(&self) -> String {
-        match &self.message {
-            &Ok(ref message) => format!("{} ok {}", self.channel_id, message.to_pon().to_string()),
-            &Err(ref err) => format!("{} err {}", self.channel_id, err.to_pon().to_string()),
+        let (status, pon) = match &self.message {
+            &Ok(ref message) => ("ok", message.to_pon()),
+            &Err(ref err) => ("err", err.to_pon()),
+        };
+        match self.encoding {
+            MessageEncoding::Pon => format!("{} {} {}", self.channel_id, status, pon.to_string()),
+            MessageEncoding::Json => format!("{} {} {}", self.channel_id, status, pon.to_json_string()),
         }
     }
 }
@@ -0,0 +1,127 @@
+use std::collections::HashSet;
+
+use pon::*;
+use document::*;
+use selector::*;
+
+// A snapshot of the dependencies between properties, for figuring out why something does or
+// doesn't update. `to_dot` renders it for GraphViz.
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct DependencyGraphNode {
+    pub prop_ref: PropRef,
+    /// None if the property isn't set, which is the case for references to properties that don't
+    /// exist yet, or if it isn't a Pon expression.
+    pub expression: Option<Pon>,
+    pub exists: bool,
+    pub volatile: bool,
+    pub invalidated: bool
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct DependencyGraphEdge {
+    pub dependent: PropRef,
+    pub dependency: PropRef
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct DependencyGraph {
+    pub nodes: Vec<DependencyGraphNode>,
+    pub edges: Vec<DependencyGraphEdge>
+}
+
+impl DependencyGraph {
+    /// Renders the graph in the GraphViz DOT format. Edges point from a property to the
+    /// properties it depends on. Missing properties are red, volatile ones dashed and invalidated
+    /// ones bold.
+    pub fn to_dot(&self) -> String {
+        let mut dot = "digraph dependencies {\n".to_string();
+        for node in &self.nodes {
+            let mut label = prop_ref_to_string(&node.prop_ref);
+            if let Some(ref expression) = node.expression {
+                label = format!("{}\n{}", label, expression.to_string());
+            }
+            let mut styles = vec![];
+            if node.volatile {
+                styles.push("dashed");
+            }
+            if node.invalidated {
+                styles.push("bold");
+            }
+            let mut attributes = vec![format!("label=\"{}\"", escape_dot(&label))];
+            if styles.len() > 0 {
+                attributes.push(format!("style=\"{}\"", styles.join(",")));
+            }
+            if !node.exists {
+                attributes.push("color=red".to_string());
+            }
+            dot.push_str(&format!("    \"{}\" [{}];\n", escape_dot(&prop_ref_to_string(&node.prop_ref)), attributes.join(", ")));
+        }
+        for edge in &self.edges {
+            dot.push_str(&format!("    \"{}\" -> \"{}\";\n",
+                escape_dot(&prop_ref_to_string(&edge.dependent)), escape_dot(&prop_ref_to_string(&edge.dependency))));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+impl Document {
+    /// The dependency graph of all properties in the document.
+    pub fn dependency_graph(&self) -> DependencyGraph {
+        let prop_refs: Vec<PropRef> = self.bus.iter().cloned().collect();
+        self.dependency_graph_for_props(prop_refs)
+    }
+    /// The dependency graph of the properties of the entities matching `selector`, along with the
+    /// properties they directly depend on or are depended on by.
+    pub fn dependency_graph_for_selector(&self, selector: &Selector) -> Result<DependencyGraph, DocError> {
+        let root = match self.get_root() {
+            Some(root) => root,
+            None => return Ok(DependencyGraph { nodes: vec![], edges: vec![] })
+        };
+        let mut prop_refs = vec![];
+        for entity_id in self.entities_iter() {
+            if selector.matches(self, root, *entity_id) {
+                prop_refs.extend(try!(self.get_properties(*entity_id)));
+            }
+        }
+        Ok(self.dependency_graph_for_props(prop_refs))
+    }
+    fn dependency_graph_for_props(&self, prop_refs: Vec<PropRef>) -> DependencyGraph {
+        let mut in_graph: HashSet<PropRef> = HashSet::new();
+        let mut edges = vec![];
+        for prop_ref in &prop_refs {
+            in_graph.insert(prop_ref.clone());
+            for dependency in self.bus.get_dependencies(prop_ref, false) {
+                in_graph.insert(dependency.clone());
+                edges.push(DependencyGraphEdge { dependent: prop_ref.clone(), dependency: dependency });
+            }
+            for dependent in self.bus.get_dependents(prop_ref, false) {
+                in_graph.insert(dependent.clone());
+                edges.push(DependencyGraphEdge { dependent: dependent, dependency: prop_ref.clone() });
+            }
+        }
+        let mut nodes: Vec<PropRef> = in_graph.into_iter().collect();
+        nodes.sort();
+        edges.sort_by(|a, b| (&a.dependent, &a.dependency).cmp(&(&b.dependent, &b.dependency)));
+        edges.dedup();
+        DependencyGraph {
+            nodes: nodes.into_iter().map(|prop_ref| DependencyGraphNode {
+                expression: self.get_property_expression(&prop_ref).ok().cloned(),
+                exists: self.bus.has(&prop_ref),
+                volatile: self.bus.is_volatile(&prop_ref),
+                invalidated: self.bus.get_invalidation_counter(&prop_ref) > 0,
+                prop_ref: prop_ref
+            }).collect(),
+            edges: edges
+        }
+    }
+}
+
+fn prop_ref_to_string(prop_ref: &PropRef) -> String {
+    format!("{}.{}", prop_ref.entity_id, prop_ref.property_key)
+}
+
+fn escape_dot(string: &str) -> String {
+    string.replace("\\", "\\\\").replace("\"", "\\\"").replace("\n", "\\n")
+}
@@ -11,6 +11,7 @@ use regex::Regex;
 pub struct DocStream {
     pub channel_id: ChannelId,
     pub client_id: ClientId,
+    pub encoding: MessageEncoding,
     pub selection: Selection,
     pub property_regex: Option<Regex>,
     pub topic: Topic
@@ -34,13 +35,13 @@ impl DocStream {
         let updated_properties: Vec<DocStreamPropertyValue> = properties.iter().map(|pr| {
                 DocStreamPropertyValue {
                     entity_id: pr.entity_id,
-                    property_key: pr.property_key.clone(),
+                    property_key: pr.property_key.to_string(),
                     property_expression: match doc.get_property_expression(&pr) {
                         Ok(v) => Some(v.clone()),
                         Err(_) => None
                     },
                     property_value: match doc.get_property_raw(pr.entity_id, &pr.property_key) {
-                        Ok(v) => Ok(format!("{:?}", v)),
+                        Ok(v) => Ok(doc.translater.value_to_pon(&v)),
                         Err(err) => Err(err.to_string())
                     }
                 }
@@ -49,6 +50,7 @@ impl DocStream {
             Some(OutgoingMessage {
                 channel_id: self.channel_id.clone(),
                 client_id: self.client_id.clone(),
+                encoding: self.encoding,
                 message: Ok(Box::new(DocStreamCycle {
                     entities_added: added,
                     entities_removed: removed,
@@ -72,13 +74,13 @@ impl DocStream {
             }).into_iter().map(|pr: PropRef| {
                 DocStreamPropertyValue {
                     entity_id: pr.entity_id,
-                    property_key: pr.property_key.clone(),
+                    property_key: pr.property_key.to_string(),
                     property_expression: match doc.get_property_expression(&pr) {
                         Ok(v) => Some(v.clone()),
                         Err(_) => None
                     },
                     property_value: match doc.get_property_raw(pr.entity_id, &pr.property_key) {
-                        Ok(v) => Ok(format!("{:?}", v)),
+                        Ok(v) => Ok(doc.translater.value_to_pon(&v)),
                         Err(err) => Err(err.to_string())
                     }
                 }
@@ -90,6 +92,7 @@ impl DocStream {
             Some(OutgoingMessage {
                 channel_id: self.channel_id.clone(),
                 client_id: self.client_id.clone(),
+                encoding: self.encoding,
                 message: Ok(Box::new(DocStreamCycle {
                     entities_added: added,
                     entities_removed: removed,
@@ -136,24 +139,18 @@ pub struct DocStreamAddedEntity {
     pub parent_id: Option<EntityId>,
     pub type_name: String
 }
-impl ToPon for DocStreamAddedEntity {
-    fn to_pon(&self) -> Pon {
-        let mut hm = HashMap::new();
-        hm.insert("entity_id".to_string(), self.entity_id.to_pon());
-        if let Some(parent_id) = self.parent_id {
-            hm.insert("parent_id".to_string(), parent_id.to_pon());
-        }
-        hm.insert("type_name".to_string(), self.type_name.to_pon());
-        Pon::Object(hm)
-    }
-}
+pon_struct!(DocStreamAddedEntity {
+    entity_id: (EntityId),
+    parent_id: (EntityId) optional,
+    type_name: (String),
+});
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct DocStreamPropertyValue {
     pub entity_id: EntityId,
     pub property_key: String,
     pub property_expression: Option<Pon>,
-    pub property_value: Result<String, String>
+    pub property_value: Result<Pon, String>
 }
 impl ToPon for DocStreamPropertyValue {
     fn to_pon(&self) -> Pon {
@@ -1,19 +1,28 @@
 use xml;
 use pon::*;
 use pon_translater::*;
+use pon_visitor::*;
+use pon_doc::*;
+use selector::*;
 use bus::*;
+use history::*;
+use observers::*;
 
 use std::fs::File;
-use std::io::BufReader;
+use std::io;
+use std::io::{BufReader, Read};
 use std::collections::HashMap;
 use std::collections::hash_map::Keys;
 use std::path::Path;
 use std::io::Write;
+use std::sync::mpsc::Receiver;
 use std::any::Any;
 use std::fmt;
 
 use xml::reader::EventReader;
+use xml::common::Position;
 use std::mem;
+use std::cmp;
 use std::borrow::Cow;
 
 #[derive(PartialEq, Debug, Clone)]
@@ -23,22 +32,45 @@ pub enum DocError {
     NoSuchEntity(EntityId),
     CantFindEntityByName(String),
     InvalidParent,
-    NotAPon
+    NotAPon,
+    TransactionInProgress,
+    NoTransaction,
+    Io { path: String, kind: io::ErrorKind, message: String },
+    /// `path` is None for documents loaded from a string. Lines and columns start at 1.
+    Xml { path: Option<String>, line: u64, column: u64, message: String },
+    /// Warnings while loading a document in `LoadMode::Strict`.
+    LoadWarnings { path: Option<String>, warnings: Vec<String> }
 }
 impl ToString for DocError {
     fn to_string(&self) -> String {
         match self {
             &DocError::BusError(ref err) => format!("BusError({})", err.to_string()),
+            &DocError::Io { ref path, ref message, .. } => format!("Failed to read {}: {}", path, message),
+            &DocError::Xml { ref path, line, column, ref message } => match path {
+                &Some(ref path) => format!("{}:{}:{}: {}", path, line, column, message),
+                &None => format!("{}:{}: {}", line, column, message)
+            },
+            &DocError::LoadWarnings { ref path, ref warnings } => format!("{} warnings while loading {}:\n{}",
+                warnings.len(), path.as_ref().map(|p| &p[..]).unwrap_or("document"), warnings.join("\n")),
             _ => format!("{:?}", self)
         }
     }
 }
 
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum LoadMode {
+    /// Fails with `DocError::LoadWarnings` if there are any warnings, such as properties that
+    /// don't parse.
+    Strict,
+    /// Loads what can be loaded and returns the warnings.
+    Lenient
+}
+
 pub type EntityId = u64;
 
 pub type EntityIter<'a> = Keys<'a, EntityId, Entity>;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Entity {
     pub id: EntityId,
     pub type_name: String,
@@ -47,23 +79,30 @@ pub struct Entity {
     pub parent_id: Option<EntityId>
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct CycleChanges {
     pub invalidations_log: Vec<InvalidatedChange>,
     pub entities_added: Vec<EntityId>,
     pub entities_removed: Vec<Entity>,
+    /// Properties that failed to evaluate, which didn't the previous cycle.
+    pub errors_started: Vec<PropRef>,
+    /// Properties that evaluate again.
+    pub errors_stopped: Vec<PropRef>,
 }
 impl CycleChanges {
     pub fn new() -> CycleChanges {
         CycleChanges {
             invalidations_log: vec![],
             entities_added: vec![],
-            entities_removed: vec![]
+            entities_removed: vec![],
+            errors_started: vec![],
+            errors_stopped: vec![]
         }
     }
     pub fn changed(&self) -> bool {
         return self.entities_added.len() > 0 || self.entities_removed.len() > 0 ||
-            self.invalidations_log.len() > 0;
+            self.invalidations_log.len() > 0 || self.errors_started.len() > 0 ||
+            self.errors_stopped.len() > 0;
     }
 }
 
@@ -80,7 +119,89 @@ pub struct Document {
     pub resources: HashMap<String, Box<Any>>,
     pub translater: PonTranslater,
     pub bus: Bus,
-    this_cycle_changes: CycleChanges
+    this_cycle_changes: CycleChanges,
+    transaction: Option<DocumentTransaction>,
+    history: Option<History>,
+    observers: Observers
+}
+
+// The state of the document when a transaction was started. The bus keeps track of its own part.
+// Entities and names are saved the first time they're changed, None meaning they didn't exist.
+// The cycle changes are only appended to, unless the cycle is closed during the transaction.
+struct DocumentTransaction {
+    id_counter: EntityId,
+    root: Option<EntityId>,
+    entities: HashMap<EntityId, Option<Entity>>,
+    entity_ids_by_name: HashMap<String, Option<EntityId>>,
+    entities_added_len: usize,
+    entities_removed_len: usize,
+    closed_cycle_changes: Option<CycleChanges>,
+    document_functions: DocumentFunctionsSnapshot
+}
+
+struct DependencyResolver<'a> {
+    document: &'a Document,
+    entity_id: EntityId
+}
+
+impl<'a> PonFolder for DependencyResolver<'a> {
+    type Error = DocError;
+    fn fold_dep_prop_ref(&mut self, named_prop_ref: &mut NamedPropRef, resolved: &mut Option<PropRef>) -> Result<(), DocError> {
+        *resolved = Some(try!(named_prop_ref.resolve(self.document, self.entity_id)));
+        Ok(())
+    }
+    // Selectors are resolved when they're used, relative to wherever they're used from
+    fn fold_selector(&mut self, _selector: &mut Selector) -> Result<(), DocError> {
+        Ok(())
+    }
+}
+
+// Entities of this type define a function named after the entity, see
+// `PonTranslater::define_document_function`.
+const FUNCTION_TYPE_NAME: &'static str = "Function";
+
+struct DocumentFunctionCalls<'a> {
+    translater: &'a PonTranslater,
+    dependencies: &'a mut Vec<PropRef>
+}
+
+impl<'a> PonVisitor for DocumentFunctionCalls<'a> {
+    fn visit_call(&mut self, call: &PonCall) {
+        if let Some(body) = self.translater.get_document_function_body(&call.function_name) {
+            self.dependencies.push(body.clone());
+        }
+        walk_call(self, call);
+    }
+    fn visit_selector(&mut self, _selector: &Selector) {}
+}
+
+struct CallsFunction<'a> {
+    function_name: &'a str,
+    found: bool
+}
+
+impl<'a> PonVisitor for CallsFunction<'a> {
+    fn visit_call(&mut self, call: &PonCall) {
+        self.found = self.found || call.function_name == self.function_name;
+        walk_call(self, call);
+    }
+}
+
+fn calls_function(expression: &Pon, function_name: &str) -> bool {
+    let mut calls = CallsFunction { function_name: function_name, found: false };
+    expression.visit(&mut calls);
+    calls.found
+}
+
+struct FunctionArguments(Vec<String>);
+
+impl PonVisitor for FunctionArguments {
+    fn visit_pon(&mut self, pon: &Pon) {
+        match pon {
+            &Pon::Argument(ref name) => self.0.push(name.clone()),
+            _ => walk_pon(self, pon)
+        }
+    }
 }
 
 impl From<BusError> for DocError {
@@ -100,6 +221,9 @@ impl Document {
             translater: translater,
             bus: Bus::new(),
             this_cycle_changes: CycleChanges::new(),
+            transaction: None,
+            history: None,
+            observers: Observers::new()
         }
     }
     pub fn new_with_root(translater: PonTranslater) -> Document {
@@ -123,7 +247,9 @@ impl Document {
             parent_id: parent_id,
             children_ids: vec![]
         };
+        self.save_entity(id);
         if let Some(parent_id) = parent_id {
+            self.save_entity(parent_id);
             let parent = match self.entities.get_mut(&parent_id) {
                 Some(parent) => parent,
                 None => return Err(DocError::InvalidParent)
@@ -136,12 +262,103 @@ impl Document {
             self.root = Some(id);
         }
         if let &Some(ref name) = &entity.name {
+            self.save_entity_name(name);
             self.entity_ids_by_name.insert(name.clone(), entity.id);
         }
         self.entities.insert(entity.id, entity);
         self.this_cycle_changes.entities_added.push(id);
+        if self.history.is_some() {
+            let snapshot = try!(self.entity_snapshot(id));
+            self.record_edit(EditOperation::AppendEntity(snapshot));
+        }
         return Ok(id);
     }
+    /// Starts a transaction. Until it's committed, all changes to entities and properties can be
+    /// undone with `rollback_transaction`. Resources are not part of the transaction.
+    pub fn begin_transaction(&mut self) -> Result<(), DocError> {
+        if self.transaction.is_some() {
+            return Err(DocError::TransactionInProgress);
+        }
+        self.transaction = Some(DocumentTransaction {
+            id_counter: self.id_counter,
+            root: self.root,
+            entities: HashMap::new(),
+            entity_ids_by_name: HashMap::new(),
+            entities_added_len: self.this_cycle_changes.entities_added.len(),
+            entities_removed_len: self.this_cycle_changes.entities_removed.len(),
+            closed_cycle_changes: None,
+            document_functions: self.translater.document_functions_snapshot()
+        });
+        self.bus.begin_transaction();
+        if let Some(ref mut history) = self.history {
+            history.close_step();
+        }
+        Ok(())
+    }
+    pub fn commit_transaction(&mut self) -> Result<(), DocError> {
+        match self.transaction.take() {
+            Some(_) => {
+                self.bus.commit_transaction();
+                if let Some(ref mut history) = self.history {
+                    history.close_step();
+                }
+                Ok(())
+            },
+            None => Err(DocError::NoTransaction)
+        }
+    }
+    /// Restores the document to exactly how it was when the transaction was started.
+    pub fn rollback_transaction(&mut self) -> Result<(), DocError> {
+        let transaction = match self.transaction.take() {
+            Some(transaction) => transaction,
+            None => return Err(DocError::NoTransaction)
+        };
+        self.id_counter = transaction.id_counter;
+        self.root = transaction.root;
+        for (id, entity) in transaction.entities {
+            match entity {
+                Some(entity) => { self.entities.insert(id, entity); },
+                None => { self.entities.remove(&id); }
+            }
+        }
+        for (name, id) in transaction.entity_ids_by_name {
+            match id {
+                Some(id) => { self.entity_ids_by_name.insert(name, id); },
+                None => { self.entity_ids_by_name.remove(&name); }
+            }
+        }
+        match transaction.closed_cycle_changes {
+            Some(cycle_changes) => self.this_cycle_changes = cycle_changes,
+            None => {
+                self.this_cycle_changes.entities_added.truncate(transaction.entities_added_len);
+                self.this_cycle_changes.entities_removed.truncate(transaction.entities_removed_len);
+            }
+        }
+        self.translater.restore_document_functions(transaction.document_functions);
+        self.bus.rollback_transaction();
+        if let Some(ref mut history) = self.history {
+            history.discard_current();
+        }
+        Ok(())
+    }
+    pub fn in_transaction(&self) -> bool {
+        self.transaction.is_some()
+    }
+    /// Runs `f` in a transaction, which is committed if it succeeds and rolled back if it fails.
+    pub fn transaction<T, F>(&mut self, f: F) -> Result<T, DocError>
+        where F: FnOnce(&mut Document) -> Result<T, DocError> {
+        try!(self.begin_transaction());
+        match f(self) {
+            Ok(value) => {
+                try!(self.commit_transaction());
+                Ok(value)
+            },
+            Err(err) => {
+                try!(self.rollback_transaction());
+                Err(err)
+            }
+        }
+    }
     pub fn get_entity_by_name(&self, name: &str) -> Option<EntityId> {
         match self.entity_ids_by_name.get(&name.to_string()) {
             Some(id) => Some(id.clone()),
@@ -157,11 +374,49 @@ impl Document {
     pub fn set_property(&mut self, entity_id: EntityId, property_key: &str, mut expression: Pon, volatile: bool) -> Result<(), DocError> {
         let prop_ref = PropRef::new(entity_id, property_key);
         try!(self.resolve_pon_dependencies(entity_id, &mut expression));
-        self.bus.set_pon(&prop_ref.clone(), volatile, expression);
+        let dependencies = self.get_pon_dependencies(&expression);
+        let defines_function = (property_key == "body" || property_key == "doc") && self.is_function_entity(entity_id);
+        if defines_function {
+            // Checked before anything is set, so that a recursive function leaves the document
+            // as it was
+            let cycle = if property_key == "body" {
+                self.find_document_function_cycle(entity_id, &expression, &dependencies)
+            } else {
+                let body = PropRef::new(entity_id, "body");
+                match self.get_property_expression(&body) {
+                    Ok(body_expression) => self.find_document_function_cycle(entity_id, body_expression, &self.bus.get_dependencies(&body, false)),
+                    Err(_) => None
+                }
+            };
+            if let Some(path) = cycle {
+                return Err(DocError::BusError(BusError::DependencyCycle { path: path }));
+            }
+        }
+        let edit = if self.history.is_some() {
+            let before = match self.get_property_expression(&prop_ref) {
+                Ok(before) => Some(Some((before.clone(), self.bus.is_volatile(&prop_ref)))),
+                // Native values can't be recorded, so replacing one isn't undoable
+                Err(DocError::NotAPon) => None,
+                Err(_) => Some(None)
+            };
+            before.map(|before| EditOperation::SetProperty { prop_ref: prop_ref.clone(), before: before, after: (expression.clone(), volatile) })
+        } else {
+            None
+        };
+        try!(self.bus.set_pon_with_dependencies(&prop_ref, volatile, expression, dependencies, &self.translater));
+        if defines_function {
+            try!(self.update_document_function(entity_id));
+        }
+        if let Some(edit) = edit {
+            self.record_edit(edit);
+        }
         Ok(())
     }
     pub fn get_property<T: BusValue>(&self, entity_id: EntityId, property_key: &str) -> Result<T, BusError> {
-        self.bus.get_typed::<T>(&PropRef::new(entity_id, property_key), &self.translater)
+        self.get_property_by_ref::<T>(&PropRef::new(entity_id, property_key))
+    }
+    pub fn get_property_by_ref<T: BusValue>(&self, prop_ref: &PropRef) -> Result<T, BusError> {
+        self.bus.get_typed::<T>(prop_ref, &self.translater)
     }
     pub fn get_property_raw(&self, entity_id: EntityId, property_key: &str) -> Result<Box<BusValue>, BusError> {
         self.bus.get(&PropRef::new(entity_id, property_key), &self.translater)
@@ -176,12 +431,38 @@ impl Document {
     pub fn has_property(&self, entity_id: EntityId, property_key: &str) -> bool {
         self.bus.has(&PropRef::new(entity_id, property_key))
     }
+    /// Ends the cycle. The bus stats are reset first, so that afterwards they show the work done
+    /// to close the cycle and the work done since.
     pub fn close_cycle(&mut self) -> CycleChanges {
+        self.bus.reset_stats();
         let mut cycle_changes = mem::replace(&mut self.this_cycle_changes, CycleChanges::new());
+        if let Some(ref mut transaction) = self.transaction {
+            if transaction.closed_cycle_changes.is_none() {
+                let mut before = CycleChanges::new();
+                before.entities_added = cycle_changes.entities_added[..transaction.entities_added_len].to_vec();
+                before.entities_removed = cycle_changes.entities_removed[..transaction.entities_removed_len].to_vec();
+                transaction.closed_cycle_changes = Some(before);
+            }
+        }
+        let (errors_started, errors_stopped) = self.bus.update_errors(&self.translater);
+        cycle_changes.errors_started = errors_started;
+        cycle_changes.errors_stopped = errors_stopped;
+        if self.transaction.is_none() {
+            if let Some(ref mut history) = self.history {
+                history.close_step();
+            }
+        }
+        cycle_changes.invalidations_log = self.bus.take_invalidations_log();
+        // Observers see the values of this cycle, which are then cleared so that volatile
+        // properties are evaluated again when they're read next cycle
+        self.with_observers(|observers, doc| observers.on_cycle(doc, &cycle_changes));
         self.bus.clear_cache();
-        cycle_changes.invalidations_log = mem::replace(&mut self.bus.invalidations_log, Vec::new());
         return cycle_changes;
     }
+    /// The properties that failed the last time they were evaluated, with their errors.
+    pub fn failing_properties(&self) -> Vec<(PropRef, BusError)> {
+        self.bus.get_errors()
+    }
     pub fn get_properties(&self, entity_id: EntityId) -> Result<Vec<PropRef>, DocError> {
         if !self.entities.contains_key(&entity_id) { return Err(DocError::NoSuchEntity(entity_id)); }
         Ok(self.get_properties_for_entity(entity_id))
@@ -249,17 +530,45 @@ impl Document {
         }
     }
     pub fn remove_entity(&mut self, entity_id: EntityId) -> Result<(), DocError> {
+        let snapshot = if self.history.is_some() {
+            Some(try!(self.entity_snapshot(entity_id)))
+        } else {
+            None
+        };
+        try!(self.remove_entity_tree(entity_id));
+        if let Some(snapshot) = snapshot {
+            self.record_edit(EditOperation::RemoveEntity(snapshot));
+        }
+        Ok(())
+    }
+    fn remove_entity_tree(&mut self, entity_id: EntityId) -> Result<(), DocError> {
+        self.save_entity(entity_id);
         match self.entities.remove(&entity_id) {
             Some(entity) => {
+                if self.root == Some(entity_id) {
+                    self.root = None;
+                }
                 self.remove_properties_for_entity(entity_id);
+                if let Some(ref name) = entity.name {
+                    let defines_function = match self.translater.get_document_function_body(name) {
+                        Some(body) => body.entity_id == entity_id,
+                        None => false
+                    };
+                    if defines_function {
+                        self.translater.remove_document_function(name);
+                        // Callers only lose dependencies here, which can't form a cycle
+                        self.relink_document_function_callers(name).unwrap();
+                    }
+                }
                 if let &Some(ref parent_id) = &entity.parent_id {
+                    self.save_entity(*parent_id);
                     match self.entities.get_mut(parent_id) {
                         Some(parent) => parent.children_ids.retain(|id| *id != entity_id),
                         None => {} // We're in a child of a removed entity and the removed entity is already removed
                     }
                 }
                 for e in &entity.children_ids {
-                    try!(self.remove_entity(*e))
+                    try!(self.remove_entity_tree(*e))
                 }
                 self.this_cycle_changes.entities_removed.push(entity);
                 Ok(())
@@ -277,6 +586,48 @@ impl Document {
         }
         Ok(())
     }
+    /// Starts recording edits so they can be undone. Edits made in a transaction are undone
+    /// together, other edits are grouped per cycle. At most `limit` steps are kept.
+    pub fn enable_history(&mut self, limit: usize) {
+        self.history = Some(History::new(limit));
+    }
+    pub fn disable_history(&mut self) {
+        self.history = None;
+    }
+    pub fn can_undo(&self) -> bool {
+        match self.history {
+            Some(ref history) => history.can_undo(),
+            None => false
+        }
+    }
+    pub fn can_redo(&self) -> bool {
+        match self.history {
+            Some(ref history) => history.can_redo(),
+            None => false
+        }
+    }
+    /// Undoes the last step of the history. Returns false if there was nothing to undo.
+    pub fn undo(&mut self) -> Result<bool, DocError> {
+        self.step_history(true)
+    }
+    /// Redoes the last undone step. Returns false if there was nothing to redo.
+    pub fn redo(&mut self) -> Result<bool, DocError> {
+        self.step_history(false)
+    }
+    /// Calls `callback` with the old and new value of the properties matching `target` that
+    /// changed, when the cycle is closed. Observers of a single property are removed when its
+    /// entity is removed.
+    pub fn observe<F: Fn(&PropertyChange) + 'static>(&mut self, target: ObserverTarget, callback: F) -> ObserverId {
+        self.with_observers(|observers, doc| observers.add_callback(doc, target, callback))
+    }
+    /// Like `observe`, but sends the changes on a channel, so they can be received on another
+    /// thread. The observer is removed when the receiver is dropped.
+    pub fn observe_channel(&mut self, target: ObserverTarget) -> (ObserverId, Receiver<PropertyChange>) {
+        self.with_observers(|observers, doc| observers.add_channel(doc, target))
+    }
+    pub fn unobserve(&mut self, id: ObserverId) -> bool {
+        self.observers.remove(id)
+    }
     pub fn reserve_entity_ids(&mut self, count: u64) -> EntityIdsReservation {
         self.id_counter += count + 1;
         EntityIdsReservation {
@@ -297,39 +648,196 @@ impl Document {
         Ok(String::from_utf8(buff).unwrap())
     }
 
+    /// Loads a document leniently, logging the warnings.
     pub fn from_file(translater: PonTranslater, path: &Path) -> Result<Document, DocError> {
-        let mut doc = Document::new(translater);
-        let mut warnings = vec![];
-        try!(doc.append_from_event_reader(&mut vec![], event_reader_from_file(path).into_iter(), &mut warnings));
-        if warnings.len() > 0 {
-            warn!("{} warnings while parsning document:", warnings.len());
-            for w in warnings {
-                warn!("{}", w);
-            }
-        }
+        let (doc, warnings) = try!(Document::from_file_with_mode(translater, path, LoadMode::Lenient));
+        log_load_warnings(&warnings);
         Ok(doc)
     }
+    pub fn from_file_with_mode(translater: PonTranslater, path: &Path, mode: LoadMode) -> Result<(Document, Vec<String>), DocError> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(err) => return Err(DocError::Io { path: path.to_string_lossy().into_owned(), kind: err.kind(), message: err.to_string() })
+        };
+        Document::load(translater, EventReader::new(BufReader::new(file)), Some(path.to_string_lossy().into_owned()), mode)
+    }
+    /// Loads a document leniently, logging the warnings.
     pub fn from_string(translater: PonTranslater, string: &str) -> Result<Document, DocError> {
+        let (doc, warnings) = try!(Document::from_string_with_mode(translater, string, LoadMode::Lenient));
+        log_load_warnings(&warnings);
+        Ok(doc)
+    }
+    pub fn from_string_with_mode(translater: PonTranslater, string: &str, mode: LoadMode) -> Result<(Document, Vec<String>), DocError> {
+        Document::load(translater, EventReader::from_str(string), None, mode)
+    }
+    fn load<R: Read>(translater: PonTranslater, parser: EventReader<R>, path: Option<String>, mode: LoadMode) -> Result<(Document, Vec<String>), DocError> {
         let mut doc = Document::new(translater);
-        let parser = EventReader::from_str(string);
         let mut warnings = vec![];
-        try!(doc.append_from_event_reader(&mut vec![], parser.into_iter(), &mut warnings));
-        if warnings.len() > 0 {
-            warn!("{} warnings while parsning document:", warnings.len());
-            for w in warnings {
-                warn!("{}", w);
+        try!(doc.append_from_event_reader(&mut vec![], parser.into_iter(), &path, &mut warnings));
+        if mode == LoadMode::Strict && warnings.len() > 0 {
+            return Err(DocError::LoadWarnings { path: path, warnings: warnings });
+        }
+        Ok((doc, warnings))
+    }
+    fn step_history(&mut self, undo: bool) -> Result<bool, DocError> {
+        if self.transaction.is_some() {
+            return Err(DocError::TransactionInProgress);
+        }
+        // Taking the history out also keeps the edits made while stepping from being recorded
+        let mut history = match self.history.take() {
+            Some(history) => history,
+            None => return Ok(false)
+        };
+        let step = if undo { history.pop_undo() } else { history.pop_redo() };
+        let result = match step {
+            Some(step) => {
+                let result = self.transaction(|doc| {
+                    if undo {
+                        for operation in step.iter().rev() {
+                            try!(doc.undo_operation(operation));
+                        }
+                    } else {
+                        for operation in &step {
+                            try!(doc.redo_operation(operation));
+                        }
+                    }
+                    Ok(())
+                });
+                // A step that fails to apply is left where it was
+                if result.is_ok() == undo {
+                    history.push_redo(step);
+                } else {
+                    history.push_undo(step);
+                }
+                result.map(|_| true)
+            },
+            None => Ok(false)
+        };
+        self.history = Some(history);
+        result
+    }
+    fn undo_operation(&mut self, operation: &EditOperation) -> Result<(), DocError> {
+        match operation {
+            &EditOperation::AppendEntity(ref snapshot) => self.remove_entity_tree(snapshot.id),
+            &EditOperation::RemoveEntity(ref snapshot) => {
+                try!(self.restore_entity_tree(snapshot));
+                self.restore_entity_properties(snapshot)
+            },
+            &EditOperation::SetProperty { ref prop_ref, ref before, .. } => match before {
+                &Some((ref expression, volatile)) => self.set_property(prop_ref.entity_id, &prop_ref.property_key, expression.clone(), volatile),
+                &None => {
+                    self.remove_property(prop_ref);
+                    Ok(())
+                }
             }
         }
-        Ok(doc)
     }
-    fn get_properties_for_entity(&self, entity_id: EntityId) -> Vec<PropRef> {
-        self.bus.iter().filter_map(|k| {
-            if k.entity_id == entity_id {
-                Some(k.clone())
-            } else {
-                None
+    fn redo_operation(&mut self, operation: &EditOperation) -> Result<(), DocError> {
+        match operation {
+            &EditOperation::AppendEntity(ref snapshot) => self.restore_entity_tree(snapshot),
+            &EditOperation::RemoveEntity(ref snapshot) => self.remove_entity_tree(snapshot.id),
+            &EditOperation::SetProperty { ref prop_ref, after: (ref expression, volatile), .. } =>
+                self.set_property(prop_ref.entity_id, &prop_ref.property_key, expression.clone(), volatile)
+        }
+    }
+    // The observers are taken out while they're used, so they can look at the document
+    fn with_observers<T, F: FnOnce(&mut Observers, &Document) -> T>(&mut self, f: F) -> T {
+        let mut observers = mem::replace(&mut self.observers, Observers::new());
+        let result = f(&mut observers, self);
+        self.observers = observers;
+        result
+    }
+    fn save_entity(&mut self, entity_id: EntityId) {
+        if let Some(ref mut transaction) = self.transaction {
+            if !transaction.entities.contains_key(&entity_id) {
+                transaction.entities.insert(entity_id, self.entities.get(&entity_id).cloned());
+            }
+        }
+    }
+    fn save_entity_name(&mut self, name: &str) {
+        if let Some(ref mut transaction) = self.transaction {
+            if !transaction.entity_ids_by_name.contains_key(name) {
+                transaction.entity_ids_by_name.insert(name.to_string(), self.entity_ids_by_name.get(name).cloned());
             }
-        }).collect()
+        }
+    }
+    fn record_edit(&mut self, operation: EditOperation) {
+        if let Some(ref mut history) = self.history {
+            history.record(operation);
+        }
+    }
+    // Only properties set to Pon expressions are part of the snapshot
+    fn entity_snapshot(&self, entity_id: EntityId) -> Result<EntitySnapshot, DocError> {
+        let entity = match self.entities.get(&entity_id) {
+            Some(entity) => entity,
+            None => return Err(DocError::NoSuchEntity(entity_id))
+        };
+        let index = match entity.parent_id {
+            Some(parent_id) => {
+                let parent = self.entities.get(&parent_id).unwrap();
+                parent.children_ids.iter().position(|x| *x == entity_id).unwrap()
+            },
+            None => 0
+        };
+        let mut properties: Vec<PropertySnapshot> = self.get_properties_for_entity(entity_id).into_iter().filter_map(|prop_ref| {
+            match self.get_property_expression(&prop_ref) {
+                Ok(expression) => Some(PropertySnapshot {
+                    key: prop_ref.property_key.to_string(),
+                    expression: expression.clone(),
+                    volatile: self.bus.is_volatile(&prop_ref)
+                }),
+                Err(_) => None
+            }
+        }).collect();
+        properties.sort_by(|a, b| a.key.cmp(&b.key));
+        let mut children = vec![];
+        for child_id in &entity.children_ids {
+            children.push(try!(self.entity_snapshot(*child_id)));
+        }
+        Ok(EntitySnapshot {
+            id: entity_id,
+            parent_id: entity.parent_id,
+            index: index,
+            type_name: entity.type_name.clone(),
+            name: entity.name.clone(),
+            properties: properties,
+            children: children
+        })
+    }
+    // Entities are restored before their properties, so that properties can refer to entities
+    // anywhere in the restored tree.
+    fn restore_entity_tree(&mut self, snapshot: &EntitySnapshot) -> Result<(), DocError> {
+        try!(self.append_entity(Some(snapshot.id), snapshot.parent_id, &snapshot.type_name, snapshot.name.clone()));
+        if let Some(parent_id) = snapshot.parent_id {
+            let parent = self.entities.get_mut(&parent_id).unwrap();
+            parent.children_ids.pop();
+            let index = cmp::min(snapshot.index, parent.children_ids.len());
+            parent.children_ids.insert(index, snapshot.id);
+        }
+        for child in &snapshot.children {
+            try!(self.restore_entity_tree(child));
+        }
+        Ok(())
+    }
+    fn restore_entity_properties(&mut self, snapshot: &EntitySnapshot) -> Result<(), DocError> {
+        for property in &snapshot.properties {
+            try!(self.set_property(snapshot.id, &property.key, property.expression.clone(), property.volatile));
+        }
+        for child in &snapshot.children {
+            try!(self.restore_entity_properties(child));
+        }
+        Ok(())
+    }
+    fn get_properties_for_entity(&self, entity_id: EntityId) -> Vec<PropRef> {
+        self.bus.iter_entity(entity_id).cloned().collect()
+    }
+    // Like `set_property`, removing the body or doc of a function entity updates the function.
+    fn remove_property(&mut self, prop_ref: &PropRef) {
+        self.bus.remove(prop_ref);
+        if (&*prop_ref.property_key == "body" || &*prop_ref.property_key == "doc") && self.is_function_entity(prop_ref.entity_id) {
+            // Without a body the function is removed, which only takes dependencies away
+            self.update_document_function(prop_ref.entity_id).unwrap();
+        }
     }
     fn remove_properties_for_entity(&mut self, entity_id: EntityId) {
         let props = self.get_properties_for_entity(entity_id);
@@ -338,30 +846,143 @@ impl Document {
         }
     }
 
-    fn resolve_pon_dependencies(&mut self, entity_id: EntityId, node: &mut Pon) -> Result<(), DocError> {
-        match node {
-            &mut Pon::Call(box PonCall { ref mut arg, .. }) =>
-                try!(self.resolve_pon_dependencies(entity_id, arg)),
-            &mut Pon::DepPropRef(ref named_prop_ref, ref mut resolved) => {
-                let prop_ref = try!(named_prop_ref.resolve(&self, entity_id));
-                *resolved = Some(prop_ref);
-            },
-            &mut Pon::Object(ref mut hm) => {
-                for (_, v) in hm.iter_mut() {
-                    try!(self.resolve_pon_dependencies(entity_id, v))
-                }
+    // The dependencies of an expression are its dependency references and the bodies of the
+    // document functions it calls.
+    fn get_pon_dependencies(&self, expression: &Pon) -> Vec<PropRef> {
+        let mut dependencies = vec![];
+        expression.build_dependencies_array(&mut dependencies);
+        expression.visit(&mut DocumentFunctionCalls { translater: &self.translater, dependencies: &mut dependencies });
+        dependencies
+    }
+
+    fn is_function_entity(&self, entity_id: EntityId) -> bool {
+        match self.entities.get(&entity_id) {
+            Some(entity) => entity.type_name == FUNCTION_TYPE_NAME,
+            None => false
+        }
+    }
+
+    fn update_document_function(&mut self, entity_id: EntityId) -> Result<(), DocError> {
+        let name = match self.entities.get(&entity_id) {
+            Some(&Entity { name: Some(ref name), .. }) => name.clone(),
+            _ => return Ok(())
+        };
+        let body = PropRef::new(entity_id, "body");
+        let is_new = self.translater.get_document_function_body(&name) != Some(&body);
+        let args = match self.get_property_expression(&body) {
+            Ok(expression) => {
+                let mut args = FunctionArguments(vec![]);
+                expression.visit(&mut args);
+                Some(args.0)
             },
-            &mut Pon::Array(ref mut arr) => {
-                for v in arr.iter_mut() {
-                    try!(self.resolve_pon_dependencies(entity_id, v))
+            Err(_) => None
+        };
+        let mut args = match args {
+            Some(args) => args,
+            None => {
+                // An entity without a body doesn't define the function
+                if !is_new {
+                    self.translater.remove_document_function(&name);
+                    // Callers only lose dependencies here, which can't form a cycle
+                    self.relink_document_function_callers(&name).unwrap();
                 }
+                return Ok(());
+            }
+        };
+        args.sort();
+        args.dedup();
+        let doc = self.get_property::<String>(entity_id, "doc").unwrap_or(String::new());
+        self.translater.define_document_function(body, PonDocFunction {
+            category: "document".to_string(),
+            module: "Document".to_string(),
+            name: name.clone(),
+            target_type_name: "Pon".to_string(),
+            arg: if args.len() == 0 {
+                PonDocMatcher::Nil
+            } else {
+                PonDocMatcher::Map(args.into_iter().map(|arg| PonDocMapField {
+                    var_name: arg,
+                    optional: false,
+                    default: None,
+                    value: PonDocMatcher::Value { typ: "Pon".to_string() }
+                }).collect())
             },
-            _ => {}
+            doc: doc,
+            // If the body calls impure functions the body property is volatile, which reaches
+            // the callers through their dependency on it
+            pure: true
+        });
+        // Changes to the body invalidate the callers through their dependency on it, but callers
+        // set before the function existed don't have that dependency yet. `set_property` rejects
+        // bodies for which that would form a cycle, but if relinking fails anyway the function
+        // is removed again and all callers are relinked without it.
+        if is_new {
+            if let Err(err) = self.relink_document_function_callers(&name) {
+                self.translater.remove_document_function(&name);
+                // Callers only lose dependencies here, which can't form a cycle
+                self.relink_document_function_callers(&name).unwrap();
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    // Defining a function makes its callers depend on its body, which forms a cycle if the body
+    // depends on one of the callers, or calls the function itself. `body_expression` and
+    // `body_dependencies` are what the body is about to be set to.
+    fn find_document_function_cycle(&self, entity_id: EntityId, body_expression: &Pon, body_dependencies: &[PropRef]) -> Option<Vec<PropRef>> {
+        let name = match self.entities.get(&entity_id) {
+            Some(&Entity { name: Some(ref name), .. }) => name,
+            _ => return None
         };
+        let body = PropRef::new(entity_id, "body");
+        // The callers of a defined function already depend on its body, so the bus finds the cycles
+        if self.translater.get_document_function_body(name) == Some(&body) {
+            return None;
+        }
+        if calls_function(body_expression, name) {
+            return Some(vec![body.clone(), body]);
+        }
+        if let Some(path) = self.bus.find_cycle(&body, body_dependencies) {
+            return Some(path);
+        }
+        for caller in self.document_function_callers(name) {
+            if caller == body {
+                continue;
+            }
+            if let Some(mut path) = self.bus.find_cycle(&caller, body_dependencies) {
+                path.insert(1, body.clone());
+                return Some(path);
+            }
+        }
+        None
+    }
+
+    // The properties set to expressions that call the function
+    fn document_function_callers(&self, function_name: &str) -> Vec<PropRef> {
+        self.bus.iter().filter(|prop_ref| match self.get_property_expression(prop_ref) {
+            Ok(expression) => calls_function(expression, function_name),
+            Err(_) => false
+        }).cloned().collect()
+    }
+
+    // Stops at the first caller whose new dependencies would form a cycle.
+    fn relink_document_function_callers(&mut self, function_name: &str) -> Result<(), DocError> {
+        let callers: Vec<(PropRef, Vec<PropRef>)> = self.document_function_callers(function_name).into_iter().map(|prop_ref| {
+            let dependencies = self.get_pon_dependencies(self.get_property_expression(&prop_ref).unwrap());
+            (prop_ref, dependencies)
+        }).collect();
+        for (prop_ref, dependencies) in callers {
+            try!(self.bus.set_dependencies(&prop_ref, dependencies));
+        }
         Ok(())
     }
 
-    fn append_from_event_reader<T: Iterator<Item=xml::reader::Result<xml::reader::XmlEvent>>>(&mut self, mut entity_stack: &mut Vec<EntityId>, mut events: T, warnings: &mut Vec<String>) -> Result<(), DocError> {
+    fn resolve_pon_dependencies(&self, entity_id: EntityId, node: &mut Pon) -> Result<(), DocError> {
+        node.fold(&mut DependencyResolver { document: self, entity_id: entity_id })
+    }
+
+    fn append_from_event_reader<T: Iterator<Item=xml::reader::Result<xml::reader::XmlEvent>>>(&mut self, mut entity_stack: &mut Vec<EntityId>, mut events: T, path: &Option<String>, warnings: &mut Vec<String>) -> Result<(), DocError> {
         while let Some(e) = events.next() {
             match e {
                 Ok(xml::reader::XmlEvent::StartElement { name: type_name, attributes, .. }) => {
@@ -388,21 +1009,8 @@ impl Document {
                                 Ok(_) => {},
                                 Err(err) => warnings.push(format!("Failed to set property {} for entity {:?}: {:?}", attribute.name.local_name, type_name.local_name, err))
                             },
-                            Err(PonParseError { line, column, expected, .. }) => {
-                                let mut pon_with_line_nrs = "".to_string();
-                                let lines: Vec<&str> = attribute.value.split("\n").collect();
-                                for i in 0..lines.len() {
-                                    pon_with_line_nrs = pon_with_line_nrs + lines[i] + "\n";
-                                    if line == i + 1 {
-                                        for _ in 1..column {
-                                            pon_with_line_nrs = pon_with_line_nrs + " ";
-                                        }
-                                        pon_with_line_nrs = pon_with_line_nrs + &format!("^ Expected: {:?}\n", expected);
-                                    }
-                                }
-                                warnings.push(format!("Parse error in {}.{}:\n{}",
-                                    type_name.local_name, attribute.name.local_name, pon_with_line_nrs))
-                            }
+                            Err(diagnostic) => warnings.push(format!("Parse error in {}.{}:\n{}",
+                                type_name.local_name, attribute.name.local_name, diagnostic.render()))
                         };
                     }
                     entity_stack.push(entity_id);
@@ -411,7 +1019,12 @@ impl Document {
                     entity_stack.pop();
                 }
                 Err(e) => {
-                    warnings.push(format!("Xml parsing error: {}", e));
+                    return Err(DocError::Xml {
+                        path: path.clone(),
+                        line: e.position().row + 1,
+                        column: e.position().column + 1,
+                        message: e.msg().to_string()
+                    });
                 }
                 _ => {}
             }
@@ -428,7 +1041,10 @@ impl Document {
                 name: xml::name::OwnedName::local(prop_ref.property_key.to_string()),
                 value: match self.get_property_expression(prop_ref) {
                     Ok(v) => v.to_string(),
-                    Err(_) => "Native Code".to_string()
+                    Err(_) => match self.bus.get(prop_ref, &self.translater) {
+                        Ok(v) => self.translater.value_to_pon(&v).to_string(),
+                        Err(err) => native_value_placeholder("error", &err.to_string()).to_string()
+                    }
                 }
             })
         }).collect();
@@ -476,11 +1092,13 @@ impl fmt::Debug for Document {
     }
 }
 
-fn event_reader_from_file(path: &Path) -> EventReader<BufReader<File>> {
-    let file = File::open(path).unwrap();
-    let file = BufReader::new(file);
-
-    EventReader::new(file)
+fn log_load_warnings(warnings: &Vec<String>) {
+    if warnings.len() > 0 {
+        warn!("{} warnings while parsing document:", warnings.len());
+        for w in warnings {
+            warn!("{}", w);
+        }
+    }
 }
 
 impl ToString for Document {
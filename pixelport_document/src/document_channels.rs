@@ -10,6 +10,7 @@ use doc_stream::*;
 use channel::*;
 use selection::*;
 use topic::*;
+use dependency_graph::*;
 use regex::*;
 
 
@@ -18,6 +19,10 @@ pub struct SetPropertiesRequest {
     pub entity: Selector,
     pub properties: HashMap<String, Pon>
 }
+pon_struct!(SetPropertiesRequest "set_properties" {
+    entity: (Selector),
+    properties: (HashMap<String, Pon>),
+});
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct AppendEntityRequest {
@@ -26,21 +31,36 @@ pub struct AppendEntityRequest {
     pub type_name: String,
     pub properties: HashMap<String, Pon>
 }
+pon_struct!(AppendEntityRequest "append_entity" {
+    entity_id: (u64) optional,
+    parent: (Selector),
+    type_name: (String),
+    properties: (HashMap<String, Pon>),
+});
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct RemoveEntityRequest {
     pub entity: Selector
 }
+pon_struct!(RemoveEntityRequest "remove_entity" {
+    entity: (Selector),
+});
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct ClearChildrenRequest {
     pub entity: Selector
 }
+pon_struct!(ClearChildrenRequest "clear_children" {
+    entity: (Selector),
+});
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct ReserveEntityIdsRequest {
     pub count: u64
 }
+pon_struct!(ReserveEntityIdsRequest "reserve_entity_ids" {
+    count: (u64),
+});
 
 
 #[derive(Debug, PartialEq, Clone)]
@@ -48,12 +68,50 @@ pub struct DocStreamCreateRequest {
     pub selector: Selector,
     pub property_regex: Option<String>
 }
+pon_struct!(DocStreamCreateRequest "doc_stream_create" {
+    selector: (Selector),
+    property_regex: (String) optional,
+});
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct CloseStreamRequest {
     pub channel_id: String
 }
+pon_struct!(CloseStreamRequest "close_stream" {
+    channel_id: (String),
+});
 
+#[derive(Debug, PartialEq, Clone)]
+pub struct DependencyGraphRequest {
+    pub selector: Option<Selector>
+}
+pon_struct!(DependencyGraphRequest "dependency_graph" {
+    selector: (Selector) optional,
+});
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct BatchRequest {
+    pub requests: Vec<Pon>
+}
+pon_struct!(BatchRequest "batch" {
+    requests: (Vec<Pon>),
+});
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct UndoRequest {
+    pub steps: u64
+}
+pon_struct!(UndoRequest "undo" {
+    steps: (u64) | 1u64,
+});
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct RedoRequest {
+    pub steps: u64
+}
+pon_struct!(RedoRequest "redo" {
+    steps: (u64) | 1u64,
+});
 
 
 macro_rules! try_find_first {
@@ -93,12 +151,20 @@ impl DocumentChannels {
         if let Some(set_properties) = (*inc.message).downcast_ref::<SetPropertiesRequest>() {
             let root_id = doc.get_root().expect("Document missing root");
             let ent = try_find_first!(inc, out, set_properties.entity, doc, root_id);
+            let mut errors = vec![];
             for (key, pon) in &set_properties.properties {
                 if let Err(err) = doc.set_property(ent, &key, pon.clone(), false) {
                     warn!("Failed to set property {} {}, error: {:?}", key, pon.to_string(), err);
+                    errors.push(format!("{}: {}", key, err.to_string()));
                 }
             }
-            out.push(inc.ok(()));
+            // The properties that could be set stay set
+            if errors.len() > 0 {
+                errors.sort();
+                out.push(inc.bad_request(&format!("Failed to set properties: {}", errors.join(", "))));
+            } else {
+                out.push(inc.ok(()));
+            }
             return true;
         }
         if let Some(append_entity) = (*inc.message).downcast_ref::<AppendEntityRequest>() {
@@ -142,12 +208,36 @@ impl DocumentChannels {
             out.push(inc.ok(vec![res.min, res.max]));
             return true;
         }
+        if let Some(batch) = (*inc.message).downcast_ref::<BatchRequest>() {
+            out.push(self.handle_batch(inc, batch, doc));
+            return true;
+        }
+        if let Some(undo) = (*inc.message).downcast_ref::<UndoRequest>() {
+            out.push(handle_history_steps(inc, doc, undo.steps, Document::undo));
+            return true;
+        }
+        if let Some(redo) = (*inc.message).downcast_ref::<RedoRequest>() {
+            out.push(handle_history_steps(inc, doc, redo.steps, Document::redo));
+            return true;
+        }
+        if let Some(dependency_graph) = (*inc.message).downcast_ref::<DependencyGraphRequest>() {
+            let graph = match &dependency_graph.selector {
+                &Some(ref selector) => doc.dependency_graph_for_selector(selector),
+                &None => Ok(doc.dependency_graph())
+            };
+            out.push(match graph {
+                Ok(graph) => inc.ok(graph.to_dot()),
+                Err(err) => inc.bad_request(&format!("Failed to create dependency graph: {}", err.to_string()))
+            });
+            return true;
+        }
         if let Some(doc_stream_create) = (*inc.message).downcast_ref::<DocStreamCreateRequest>() {
             let root_id = doc.get_root().expect("Document missing root");
             let selection = Selection::new(doc_stream_create.selector.clone(), root_id);
             let mut doc_stream = DocStream {
                 channel_id: inc.channel_id.clone(),
                 client_id: inc.client_id.clone(),
+                encoding: inc.encoding,
                 selection: selection,
                 property_regex: match &doc_stream_create.property_regex {
                     &Some(ref regex) => Some(Regex::new(regex).expect("Non parseable regex")),
@@ -172,92 +262,112 @@ impl DocumentChannels {
         return false;
     }
 
-    pub fn pon_document_channels(translater: &mut PonTranslater) {
-        pon_register_functions!("channels", "Document", translater =>
+    // Runs the requests of a batch in a transaction, answering with the list of their responses
+    // if they all succeed. Only requests that change the document can be batched.
+    fn handle_batch(&mut self, inc: &IncomingMessage, batch: &BatchRequest, doc: &mut Document) -> OutgoingMessage {
+        if let Err(err) = doc.begin_transaction() {
+            return inc.bad_request(&format!("Failed to start batch: {}", err.to_string()));
+        }
+        let mut responses = vec![];
+        for (i, request) in batch.requests.iter().enumerate() {
+            let message = match doc.translater.translate_raw(request, &doc.bus) {
+                Ok(message) => message,
+                Err(err) => {
+                    doc.rollback_transaction().unwrap();
+                    return inc.bad_request(&format!("Unable to translate batch request {}: {}", i, err.to_string()));
+                }
+            };
+            if !is_batchable(&message) {
+                doc.rollback_transaction().unwrap();
+                return inc.bad_request(&format!("Batch request {} can't be batched: {}", i, request.to_string()));
+            }
+            let inner = IncomingMessage {
+                client_id: inc.client_id.clone(),
+                channel_id: inc.channel_id.clone(),
+                encoding: inc.encoding,
+                message: message
+            };
+            let mut inner_out = vec![];
+            self.handle_request(&inner, &mut inner_out, doc);
+            for response in inner_out {
+                match response.message {
+                    Ok(message) => responses.push(message.to_pon()),
+                    Err(err) => {
+                        doc.rollback_transaction().unwrap();
+                        return inc.bad_request(&format!("Batch request {} failed: {}", i, err.message));
+                    }
+                }
+            }
+        }
+        doc.commit_transaction().unwrap();
+        inc.ok(responses)
+    }
 
+    pub fn pon_document_channels(translater: &mut PonTranslater) {
+        translater.register_pon_call::<SetPropertiesRequest>("channels", "Document",
 r#"Set properties of an entity. Dependencies and functions in `properties` are not evaluated at
 call time.
 
 For instance, in `set_properties { entity: root, properties: { x: @root.y } }` the `@root.y` will
-not be evaluated at request time, but rather set up as a dependency in the document."#,
-            set_properties({
-                entity: (Selector),
-                properties: {Pon},
-            }) SetPropertiesRequest => {
-                Ok(SetPropertiesRequest {
-                    entity: entity,
-                    properties: properties
-                })
-            }
+not be evaluated at request time, but rather set up as a dependency in the document."#);
 
+        translater.register_pon_call::<AppendEntityRequest>("channels", "Document",
             r#"Append an entity to a parent entity. Properties are not evaluted at request time (see
-            set_properties for details)"#,
-            append_entity({
-                entity_id: (f32) optional,
-                parent: (Selector),
-                type_name: (String),
-                properties: {Pon},
-            }) AppendEntityRequest => {
-                Ok(AppendEntityRequest {
-                    entity_id: match entity_id {
-                        Some(v) => Some(v as EntityId),
-                        None => None
-                    },
-                    parent: parent,
-                    type_name: type_name,
-                    properties: properties
-                })
-            }
+            set_properties for details)"#);
 
-            "Remove an entity.",
-            remove_entity({
-                entity: (Selector),
-            }) RemoveEntityRequest => {
-                Ok(RemoveEntityRequest {
-                    entity: entity
-                })
-            }
+        translater.register_pon_call::<RemoveEntityRequest>("channels", "Document",
+            "Remove an entity.");
 
-            "Clear children of an entity.",
-            clear_children({
-                entity: (Selector),
-            }) ClearChildrenRequest => {
-                Ok(ClearChildrenRequest {
-                    entity: entity
-                })
-            }
+        translater.register_pon_call::<ClearChildrenRequest>("channels", "Document",
+            "Clear children of an entity.");
 
+        translater.register_pon_call::<ReserveEntityIdsRequest>("channels", "Document",
+            "Reserve a number of entity ids, that can then be used in append_entity.");
 
-            "Reserve a number of entity ids, that can then be used in append_entity.",
-            reserve_entity_ids({
-                count: (f32),
-            }) ReserveEntityIdsRequest => {
-                Ok(ReserveEntityIdsRequest {
-                    count: count as u64
-                })
-            }
+        translater.register_pon_call::<BatchRequest>("channels", "Document",
+            r#"Run a list of set_properties, append_entity, remove_entity, clear_children and
+            reserve_entity_ids requests as a unit. If one of them fails, the changes made by the
+            others are undone. Responds with the list of the responses of each request."#);
+
+        translater.register_pon_call::<UndoRequest>("channels", "Document",
+            r#"Undo the last `steps` steps of the document history. A step is a transaction or the
+            changes of one cycle. Responds with the number of steps undone."#);
+
+        translater.register_pon_call::<RedoRequest>("channels", "Document",
+            "Redo the last `steps` undone steps. Responds with the number of steps redone.");
 
+        translater.register_pon_call::<DependencyGraphRequest>("channels", "Document",
+            r#"Get the dependencies between properties as a GraphViz DOT graph. Only includes the
+            properties of the entities matching `selector`, and their direct dependencies and
+            dependents, if given."#);
+
+        translater.register_pon_call::<DocStreamCreateRequest>("channels", "Document",
             r#"Create a doc stream. Streams changes to the document, filtered by `selector` and
-            optionally `property_regex`."#,
-            doc_stream_create({
-                selector: (Selector),
-                property_regex: (String) optional,
-            }) DocStreamCreateRequest => {
-                Ok(DocStreamCreateRequest {
-                    selector: selector,
-                    property_regex: property_regex,
-                })
-            }
+            optionally `property_regex`."#);
 
-            "Remove a stream previously created",
-            close_stream({
-                channel_id: (String),
-            }) CloseStreamRequest => {
-                Ok(CloseStreamRequest {
-                    channel_id: channel_id,
-                })
-            }
-        );
+        translater.register_pon_call::<CloseStreamRequest>("channels", "Document",
+            "Remove a stream previously created");
     }
 
 }
+
+fn handle_history_steps<F>(inc: &IncomingMessage, doc: &mut Document, steps: u64, step: F) -> OutgoingMessage
+    where F: Fn(&mut Document) -> Result<bool, DocError> {
+    let mut done = 0;
+    while done < steps {
+        match step(doc) {
+            Ok(true) => done += 1,
+            Ok(false) => break,
+            Err(err) => return inc.bad_request(&format!("Failed after {} steps: {}", done, err.to_string()))
+        }
+    }
+    inc.ok(done)
+}
+
+fn is_batchable(message: &Box<BusValue>) -> bool {
+    (**message).is::<SetPropertiesRequest>() ||
+        (**message).is::<AppendEntityRequest>() ||
+        (**message).is::<RemoveEntityRequest>() ||
+        (**message).is::<ClearChildrenRequest>() ||
+        (**message).is::<ReserveEntityIdsRequest>()
+}
@@ -4,7 +4,7 @@ use pon::*;
 use bus::*;
 
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum EntityMatch {
     Any,
     Name(String),
@@ -39,11 +39,11 @@ impl EntityMatch {
                 _ => false
             },
             &EntityMatch::PropertyValueEquals { ref property, box ref value } => match document.get_property_raw(entity_id, property) {
-                Ok(val) => (*val).bus_value_equals(&document.translater.translate_raw(value, &document.bus).unwrap()),
+                Ok(val) => property_value_equals(&val, &document.translater.translate_raw(value, &document.bus).unwrap()),
                 Err(_) => false
             },
             &EntityMatch::PropertyValueNotEquals { ref property, box ref value } => match document.get_property_raw(entity_id, property) {
-                Ok(val) => !(*val).bus_value_equals(&document.translater.translate_raw(value, &document.bus).unwrap()),
+                Ok(val) => !property_value_equals(&val, &document.translater.translate_raw(value, &document.bus).unwrap()),
                 Err(_) => true
             },
             &EntityMatch::PropertyExists(ref property) => document.has_property(entity_id, property),
@@ -69,14 +69,39 @@ impl EntityMatch {
     }
 }
 
+// `[x=5]` should match both x="5" and x="5.0"
+fn property_value_equals(a: &Box<BusValue>, b: &Box<BusValue>) -> bool {
+    if (**a).bus_value_equals(b) {
+        return true;
+    }
+    match (number_value(a), number_value(b)) {
+        (Some(a), Some(b)) => a == b,
+        _ => false
+    }
+}
+
+fn number_value(value: &Box<BusValue>) -> Option<f64> {
+    if let Some(v) = (**value).downcast_ref::<f32>() {
+        Some(*v as f64)
+    } else if let Some(v) = (**value).downcast_ref::<i64>() {
+        Some(*v as f64)
+    } else {
+        None
+    }
+}
+
 impl ToString for EntityMatch {
     fn to_string(&self) -> String {
         match self {
             &EntityMatch::Any => "*".to_string(),
             &EntityMatch::TypeName(ref name) => format!("{}", name),
-            &EntityMatch::Name(ref name) => format!("[name={}]", name),
-            &EntityMatch::PropertyValueEquals { ref property, ref value } => format!("[{}={}]", property, value.to_string()),
-            &EntityMatch::PropertyValueNotEquals { ref property, ref value } => format!("[{}!={}]", property, value.to_string()),
+            &EntityMatch::Name(ref name) => if is_pon_identifier(name) {
+                format!("[name={}]", name)
+            } else {
+                format!("[name={}]", stringify_pon_string(name))
+            },
+            &EntityMatch::PropertyValueEquals { ref property, ref value } => format!("[{}={}]", property, value.to_operand_string()),
+            &EntityMatch::PropertyValueNotEquals { ref property, ref value } => format!("[{}!={}]", property, value.to_operand_string()),
             &EntityMatch::PropertyExists(ref property) => format!("[{}]", property),
             &EntityMatch::And(ref a, ref b) => format!("[{} && {}]", a.to_string(), b.to_string()),
             &EntityMatch::Or(ref a, ref b) => format!("[{} || {}]", a.to_string(), b.to_string())
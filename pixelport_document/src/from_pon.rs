@@ -0,0 +1,281 @@
+use pon::*;
+use bus::*;
+use pon_translater::*;
+use pon_doc::*;
+use selector::*;
+
+use std::collections::HashMap;
+use cgmath::{Vector2, Vector3, Vector4, Matrix4};
+
+/// The inverse of `ToPon`. Dependencies and functions in the pon are evaluated through the
+/// translater, except where the target type is `Pon` itself, which is taken as is.
+pub trait FromPon: Sized {
+    fn from_pon(pon: &Pon, translater: &PonTranslater, bus: &Bus) -> Result<Self, PonTranslaterErr>;
+    /// Describes the pon `from_pon` accepts, for documentation.
+    fn pon_doc_matcher() -> PonDocMatcher;
+}
+
+/// Types written as a call, `function_name arg`, which can be registered with
+/// `PonTranslater::register_pon_call`. Implemented by `pon_struct!` when given a function name.
+pub trait FromPonCall: FromPon + ToPon + BusValue {
+    fn function_name() -> &'static str;
+    fn type_name() -> &'static str;
+    fn from_pon_arg(arg: &Pon, translater: &PonTranslater, bus: &Bus) -> Result<Self, PonTranslaterErr>;
+    fn arg_pon_doc_matcher() -> PonDocMatcher;
+}
+
+/// `FromPon::from_pon` for call types. Calls to other functions, and dependencies, are left to the
+/// translater.
+pub fn from_pon_call<T: FromPonCall>(pon: &Pon, translater: &PonTranslater, bus: &Bus) -> Result<T, PonTranslaterErr> {
+    match pon {
+        &Pon::Call(box PonCall { ref function_name, ref arg }) if function_name == T::function_name() =>
+            T::from_pon_arg(arg, translater, bus),
+        _ => translater.translate::<T>(pon, bus)
+    }
+}
+
+/// Generates `FromPon` and `ToPon` for a struct. With a function name the struct is written as a
+/// call, `set_properties { ... }`, and also gets `FromPonCall`; without one it's a plain object.
+/// Fields use the same syntax as `pon_register_functions!`, with `optional` fields being `Option`s.
+///
+/// ```ignore
+/// pon_struct!(AppendEntityRequest "append_entity" {
+///     entity_id: (u64) optional,
+///     parent: (Selector),
+///     type_name: (String) | "Entity",
+/// });
+/// ```
+#[macro_export]
+macro_rules! pon_struct {
+    (@fields $typ:ident [$($function_name:tt)*] [$($done:tt)*] $field:ident : ($ftyp:ty) optional, $($rest:tt)*) => (
+        pon_struct!(@fields $typ [$($function_name)*] [$($done)* ($field ($ftyp) optional [])] $($rest)*);
+    );
+    (@fields $typ:ident [$($function_name:tt)*] [$($done:tt)*] $field:ident : ($ftyp:ty) | $default:expr, $($rest:tt)*) => (
+        pon_struct!(@fields $typ [$($function_name)*] [$($done)* ($field ($ftyp) default [$default])] $($rest)*);
+    );
+    (@fields $typ:ident [$($function_name:tt)*] [$($done:tt)*] $field:ident : ($ftyp:ty), $($rest:tt)*) => (
+        pon_struct!(@fields $typ [$($function_name)*] [$($done)* ($field ($ftyp) required [])] $($rest)*);
+    );
+    (@fields $typ:ident [] [$(($field:ident ($ftyp:ty) $kind:ident [$($default:expr),*]))*]) => (
+        impl $crate::from_pon::FromPon for $typ {
+            fn from_pon(pon: &$crate::pon::Pon, translater: &$crate::pon_translater::PonTranslater, bus: &$crate::bus::Bus) -> Result<$typ, $crate::pon_translater::PonTranslaterErr> {
+                let map = try!(translater.translate::<::std::collections::HashMap<String, $crate::pon::Pon>>(pon, bus));
+                $( let $field = pon_struct!(@from_field map, translater, bus, $field ($ftyp) $kind [$($default),*]); )*
+                Ok($typ { $($field: $field),* })
+            }
+            fn pon_doc_matcher() -> $crate::pon_doc::PonDocMatcher {
+                $crate::pon_doc::PonDocMatcher::Map(vec![$( pon_struct!(@doc_field $field ($ftyp) $kind [$($default),*]) ),*])
+            }
+        }
+        impl $crate::pon::ToPon for $typ {
+            fn to_pon(&self) -> $crate::pon::Pon {
+                let mut map = ::std::collections::HashMap::new();
+                $( pon_struct!(@to_field map, $field $kind, &self.$field); )*
+                $crate::pon::Pon::Object(map)
+            }
+        }
+    );
+    (@fields $typ:ident [$function_name:tt] [$(($field:ident ($ftyp:ty) $kind:ident [$($default:expr),*]))*]) => (
+        impl $crate::from_pon::FromPon for $typ {
+            fn from_pon(pon: &$crate::pon::Pon, translater: &$crate::pon_translater::PonTranslater, bus: &$crate::bus::Bus) -> Result<$typ, $crate::pon_translater::PonTranslaterErr> {
+                $crate::from_pon::from_pon_call(pon, translater, bus)
+            }
+            fn pon_doc_matcher() -> $crate::pon_doc::PonDocMatcher {
+                $crate::pon_doc::PonDocMatcher::Value { typ: stringify!($typ).to_string() }
+            }
+        }
+        impl $crate::from_pon::FromPonCall for $typ {
+            fn function_name() -> &'static str {
+                $function_name
+            }
+            fn type_name() -> &'static str {
+                stringify!($typ)
+            }
+            fn from_pon_arg(arg: &$crate::pon::Pon, translater: &$crate::pon_translater::PonTranslater, bus: &$crate::bus::Bus) -> Result<$typ, $crate::pon_translater::PonTranslaterErr> {
+                let map = try!(translater.translate::<::std::collections::HashMap<String, $crate::pon::Pon>>(arg, bus));
+                $( let $field = pon_struct!(@from_field map, translater, bus, $field ($ftyp) $kind [$($default),*]); )*
+                Ok($typ { $($field: $field),* })
+            }
+            fn arg_pon_doc_matcher() -> $crate::pon_doc::PonDocMatcher {
+                $crate::pon_doc::PonDocMatcher::Map(vec![$( pon_struct!(@doc_field $field ($ftyp) $kind [$($default),*]) ),*])
+            }
+        }
+        impl $crate::pon::ToPon for $typ {
+            fn to_pon(&self) -> $crate::pon::Pon {
+                let mut map = ::std::collections::HashMap::new();
+                $( pon_struct!(@to_field map, $field $kind, &self.$field); )*
+                $crate::pon::Pon::call($function_name, $crate::pon::Pon::Object(map))
+            }
+        }
+    );
+
+    (@from_field $map:ident, $translater:ident, $bus:ident, $field:ident ($ftyp:ty) required []) => (
+        match $map.get(stringify!($field)) {
+            Some(v) => try!(<$ftyp as $crate::from_pon::FromPon>::from_pon(v, $translater, $bus)),
+            None => return Err($crate::pon_translater::PonTranslaterErr::RequiredFieldMissing { field: From::from(stringify!($field)) })
+        }
+    );
+    (@from_field $map:ident, $translater:ident, $bus:ident, $field:ident ($ftyp:ty) optional []) => (
+        match $map.get(stringify!($field)) {
+            Some(v) => Some(try!(<$ftyp as $crate::from_pon::FromPon>::from_pon(v, $translater, $bus))),
+            None => None
+        }
+    );
+    (@from_field $map:ident, $translater:ident, $bus:ident, $field:ident ($ftyp:ty) default [$default:expr]) => (
+        match $map.get(stringify!($field)) {
+            Some(v) => try!(<$ftyp as $crate::from_pon::FromPon>::from_pon(v, $translater, $bus)),
+            None => From::from($default)
+        }
+    );
+
+    (@to_field $map:ident, $field:ident optional, $value:expr) => (
+        if let &Some(ref v) = $value {
+            $map.insert(stringify!($field).to_string(), $crate::pon::ToPon::to_pon(v));
+        }
+    );
+    (@to_field $map:ident, $field:ident $kind:ident, $value:expr) => (
+        $map.insert(stringify!($field).to_string(), $crate::pon::ToPon::to_pon($value));
+    );
+
+    (@doc_field $field:ident ($ftyp:ty) required []) => (
+        $crate::pon_doc::PonDocMapField {
+            var_name: stringify!($field).to_string(),
+            optional: false,
+            default: None,
+            value: <$ftyp as $crate::from_pon::FromPon>::pon_doc_matcher()
+        }
+    );
+    (@doc_field $field:ident ($ftyp:ty) optional []) => (
+        $crate::pon_doc::PonDocMapField {
+            var_name: stringify!($field).to_string(),
+            optional: true,
+            default: None,
+            value: <$ftyp as $crate::from_pon::FromPon>::pon_doc_matcher()
+        }
+    );
+    (@doc_field $field:ident ($ftyp:ty) default [$default:expr]) => (
+        $crate::pon_doc::PonDocMapField {
+            var_name: stringify!($field).to_string(),
+            optional: false,
+            default: Some($crate::pon::ToPon::to_pon(&$default).to_string()),
+            value: <$ftyp as $crate::from_pon::FromPon>::pon_doc_matcher()
+        }
+    );
+
+    ($typ:ident $function_name:tt { $($fields:tt)* }) => (
+        pon_struct!(@fields $typ [$function_name] [] $($fields)*);
+    );
+    ($typ:ident { $($fields:tt)* }) => (
+        pon_struct!(@fields $typ [] [] $($fields)*);
+    );
+}
+
+/// Generates `FromPon` and `ToPon` for an enum of unit variants, written as strings.
+///
+/// ```ignore
+/// pon_enum!(RequestErrorType {
+///     BadRequest => "bad_request",
+///     InternalError => "internal_error",
+/// });
+/// ```
+#[macro_export]
+macro_rules! pon_enum {
+    ($typ:ident { $($variant:ident => $name:expr,)+ }) => (
+        impl $crate::from_pon::FromPon for $typ {
+            fn from_pon(pon: &$crate::pon::Pon, translater: &$crate::pon_translater::PonTranslater, bus: &$crate::bus::Bus) -> Result<$typ, $crate::pon_translater::PonTranslaterErr> {
+                let value = try!(translater.translate::<String>(pon, bus));
+                $(
+                if value == $name {
+                    return Ok($typ::$variant);
+                }
+                )+
+                Err($crate::pon_translater::PonTranslaterErr::EnumValueError {
+                    expected_on_of: vec![$(format!("{:?}", $name),)+],
+                    found: format!("{:?}", value)
+                })
+            }
+            fn pon_doc_matcher() -> $crate::pon_doc::PonDocMatcher {
+                $crate::pon_doc::PonDocMatcher::Enum(vec![$(
+                    $crate::pon_doc::PonDocEnumOption { name: $name.to_string() },
+                )+])
+            }
+        }
+        impl $crate::pon::ToPon for $typ {
+            fn to_pon(&self) -> $crate::pon::Pon {
+                match self {
+                    $(
+                    &$typ::$variant => $crate::pon::Pon::String($name.to_string()),
+                    )+
+                }
+            }
+        }
+    );
+}
+
+// Types that are produced by translating the pon as is
+macro_rules! from_pon_by_translate {
+    ($($typ:ty),*) => ($(
+        impl FromPon for $typ {
+            fn from_pon(pon: &Pon, translater: &PonTranslater, bus: &Bus) -> Result<$typ, PonTranslaterErr> {
+                translater.translate::<$typ>(pon, bus)
+            }
+            fn pon_doc_matcher() -> PonDocMatcher {
+                PonDocMatcher::Value { typ: stringify!($typ).to_string() }
+            }
+        }
+    )*)
+}
+
+from_pon_by_translate!(f32, i64, u64, bool, String, Selector, NamedPropRef,
+    Vector2<f32>, Vector3<f32>, Vector4<f32>, Matrix4<f32>);
+
+impl FromPon for Pon {
+    fn from_pon(pon: &Pon, _: &PonTranslater, _: &Bus) -> Result<Pon, PonTranslaterErr> {
+        Ok(pon.clone())
+    }
+    fn pon_doc_matcher() -> PonDocMatcher {
+        PonDocMatcher::Value { typ: "Pon".to_string() }
+    }
+}
+
+impl FromPon for () {
+    fn from_pon(pon: &Pon, translater: &PonTranslater, bus: &Bus) -> Result<(), PonTranslaterErr> {
+        translater.translate::<()>(pon, bus)
+    }
+    fn pon_doc_matcher() -> PonDocMatcher {
+        PonDocMatcher::Nil
+    }
+}
+
+fn doc_matcher_type_name(matcher: PonDocMatcher) -> String {
+    match matcher {
+        PonDocMatcher::Value { typ } => typ,
+        matcher => matcher.generate_usage(0)
+    }
+}
+
+impl<T: FromPon> FromPon for Vec<T> {
+    fn from_pon(pon: &Pon, translater: &PonTranslater, bus: &Bus) -> Result<Vec<T>, PonTranslaterErr> {
+        let mut arr = vec![];
+        for v in &try!(translater.translate::<Vec<Pon>>(pon, bus)) {
+            arr.push(try!(T::from_pon(v, translater, bus)));
+        }
+        Ok(arr)
+    }
+    fn pon_doc_matcher() -> PonDocMatcher {
+        PonDocMatcher::Array { typ: doc_matcher_type_name(T::pon_doc_matcher()) }
+    }
+}
+
+impl<T: FromPon> FromPon for HashMap<String, T> {
+    fn from_pon(pon: &Pon, translater: &PonTranslater, bus: &Bus) -> Result<HashMap<String, T>, PonTranslaterErr> {
+        let mut map = HashMap::new();
+        for (k, v) in &try!(translater.translate::<HashMap<String, Pon>>(pon, bus)) {
+            map.insert(k.to_string(), try!(T::from_pon(v, translater, bus)));
+        }
+        Ok(map)
+    }
+    fn pon_doc_matcher() -> PonDocMatcher {
+        PonDocMatcher::Object { typ: doc_matcher_type_name(T::pon_doc_matcher()) }
+    }
+}
@@ -0,0 +1,93 @@
+use std::collections::VecDeque;
+
+use pon::*;
+use document::*;
+
+// Undo/redo history of a document. Every edit is recorded together with what's needed to invert
+// it, and edits are grouped into steps: one per transaction, or one per cycle for edits made
+// outside a transaction.
+
+#[derive(Debug, Clone)]
+pub struct PropertySnapshot {
+    pub key: String,
+    pub expression: Pon,
+    pub volatile: bool
+}
+
+#[derive(Debug, Clone)]
+pub struct EntitySnapshot {
+    pub id: EntityId,
+    pub parent_id: Option<EntityId>,
+    /// Position among the children of the parent.
+    pub index: usize,
+    pub type_name: String,
+    pub name: Option<String>,
+    pub properties: Vec<PropertySnapshot>,
+    pub children: Vec<EntitySnapshot>
+}
+
+#[derive(Debug, Clone)]
+pub enum EditOperation {
+    /// The snapshot has no properties or children; those are recorded as separate operations.
+    AppendEntity(EntitySnapshot),
+    RemoveEntity(EntitySnapshot),
+    /// `before` is None if the property didn't exist.
+    SetProperty { prop_ref: PropRef, before: Option<(Pon, bool)>, after: (Pon, bool) }
+}
+
+#[derive(Debug)]
+pub struct History {
+    limit: usize,
+    undo_steps: VecDeque<Vec<EditOperation>>,
+    redo_steps: Vec<Vec<EditOperation>>,
+    current: Vec<EditOperation>
+}
+
+impl History {
+    pub fn new(limit: usize) -> History {
+        History {
+            limit: limit,
+            undo_steps: VecDeque::new(),
+            redo_steps: Vec::new(),
+            current: Vec::new()
+        }
+    }
+    /// Records an edit in the current step. A new edit makes the redo steps obsolete.
+    pub fn record(&mut self, operation: EditOperation) {
+        self.current.push(operation);
+        self.redo_steps.clear();
+    }
+    /// Ends the current step, dropping the oldest steps if there are more than `limit`.
+    pub fn close_step(&mut self) {
+        if self.current.len() > 0 {
+            let step = self.current.drain(..).collect();
+            self.push_undo(step);
+        }
+    }
+    /// Forgets the edits of the current step, for when they've been rolled back.
+    pub fn discard_current(&mut self) {
+        self.current.clear();
+    }
+    pub fn can_undo(&self) -> bool {
+        self.current.len() > 0 || self.undo_steps.len() > 0
+    }
+    pub fn can_redo(&self) -> bool {
+        self.redo_steps.len() > 0
+    }
+    pub fn pop_undo(&mut self) -> Option<Vec<EditOperation>> {
+        self.close_step();
+        self.undo_steps.pop_back()
+    }
+    pub fn pop_redo(&mut self) -> Option<Vec<EditOperation>> {
+        self.redo_steps.pop()
+    }
+    pub fn push_undo(&mut self, step: Vec<EditOperation>) {
+        self.undo_steps.push_back(step);
+        while self.undo_steps.len() > self.limit {
+            self.undo_steps.pop_front();
+        }
+    }
+    pub fn push_redo(&mut self, step: Vec<EditOperation>) {
+        self.redo_steps.push(step);
+    }
+}
@@ -1,8 +1,8 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::mem;
 use std::hash::Hash;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct InvProp<K> {
     dependencies: Vec<K>,
     dependents: Vec<K>,
@@ -18,9 +18,12 @@ impl<K> InvProp<K> {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct InverseDependenciesCounter<K: Eq + Hash> {
-    props: HashMap<K, InvProp<K>>
+    props: HashMap<K, InvProp<K>>,
+    // The props as they were when the transaction was started, saved the first time they're
+    // changed. None means the prop didn't exist.
+    transaction: Option<HashMap<K, Option<InvProp<K>>>>
 }
 
 #[derive(Debug, PartialEq)]
@@ -41,22 +44,51 @@ impl<K> ChangedNonZero<K> {
 impl<K: Eq + Hash + Clone> InverseDependenciesCounter<K> {
     pub fn new() -> InverseDependenciesCounter<K> {
         InverseDependenciesCounter {
-            props: HashMap::new()
+            props: HashMap::new(),
+            transaction: None
         }
     }
+    /// Starts saving the props that change, so that they can be restored with
+    /// `rollback_transaction`. Starting a transaction while another is in progress does nothing.
+    pub fn begin_transaction(&mut self) {
+        if self.transaction.is_none() {
+            self.transaction = Some(HashMap::new());
+        }
+    }
+    pub fn commit_transaction(&mut self) {
+        self.transaction = None;
+    }
+    pub fn rollback_transaction(&mut self) {
+        if let Some(saved) = self.transaction.take() {
+            for (key, prop) in saved {
+                match prop {
+                    Some(prop) => { self.props.insert(key, prop); },
+                    None => { self.props.remove(&key); }
+                }
+            }
+        }
+    }
+    fn prop_mut(&mut self, key: &K) -> &mut InvProp<K> {
+        if let Some(ref mut saved) = self.transaction {
+            if !saved.contains_key(key) {
+                saved.insert(key.clone(), self.props.get(key).cloned());
+            }
+        }
+        self.props.entry(key.clone()).or_insert(InvProp::new())
+    }
     pub fn set_dependencies(&mut self, key: &K, dependencies: Vec<K>, change: &mut ChangedNonZero<K>) {
         // This pr depends on a all these dependencies and together they have all invalidated this
         // one `uninvalidate` number of times.
         let mut uninvalidate = 0;
         let old_dependencies = {
-            let p = self.props.entry(key.clone()).or_insert(InvProp::new());
+            let p = self.prop_mut(key);
             if p.dependencies == dependencies {
                 return;
             }
             mem::replace(&mut p.dependencies, dependencies.clone())
         };
         for d in old_dependencies {
-            let p = self.props.entry(d).or_insert(InvProp::new());
+            let p = self.prop_mut(&d);
             p.dependents.retain(|x| !x.eq(&key));
             uninvalidate += p.counter;
         }
@@ -64,7 +96,7 @@ impl<K: Eq + Hash + Clone> InverseDependenciesCounter<K> {
         // different amount of times they want to invalidate this thing
         let mut reinvalidate = 0;
         for d in dependencies {
-            let p = self.props.entry(d).or_insert(InvProp::new());
+            let p = self.prop_mut(&d);
             p.dependents.push(key.clone());
             reinvalidate += p.counter;
         }
@@ -74,9 +106,39 @@ impl<K: Eq + Hash + Clone> InverseDependenciesCounter<K> {
             self.change_counter_recursively(key.clone(), reinvalidate - uninvalidate, change);
         }
     }
+    /// If making `key` depend on `dependencies` would create a cycle, returns the cycle as a path
+    /// that starts and ends with `key`. `set_dependencies` must not be called with a cycle.
+    pub fn find_cycle(&self, key: &K, dependencies: &[K]) -> Option<Vec<K>> {
+        let mut visited = HashSet::new();
+        for d in dependencies {
+            let mut path = vec![key.clone()];
+            if self.find_path(d, key, &mut visited, &mut path) {
+                return Some(path);
+            }
+        }
+        None
+    }
+    fn find_path(&self, from: &K, to: &K, visited: &mut HashSet<K>, path: &mut Vec<K>) -> bool {
+        path.push(from.clone());
+        if from == to {
+            return true;
+        }
+        if visited.insert(from.clone()) {
+            if let Some(p) = self.props.get(from) {
+                for d in &p.dependencies {
+                    if self.find_path(d, to, visited, path) {
+                        return true;
+                    }
+                }
+            }
+        }
+        path.pop();
+        false
+    }
     pub fn remove_property(&mut self, key: &K) -> ChangedNonZero<K> {
         let mut change = ChangedNonZero::new();
         self.set_dependencies(key, Vec::new(), &mut change);
+        self.prop_mut(key);
         self.props.remove(key);
         change
     }
@@ -86,6 +148,31 @@ impl<K: Eq + Hash + Clone> InverseDependenciesCounter<K> {
             None => false
         }
     }
+    pub fn get_counter(&self, key: &K) -> i32 {
+        match self.props.get(key) {
+            Some(p) => p.counter,
+            None => 0
+        }
+    }
+    pub fn get_dependencies(&self, key: &K) -> Vec<K> {
+        match self.props.get(key) {
+            Some(p) => p.dependencies.clone(),
+            None => Vec::new()
+        }
+    }
+    pub fn get_dependents(&self, key: &K) -> Vec<K> {
+        match self.props.get(key) {
+            Some(p) => p.dependents.clone(),
+            None => Vec::new()
+        }
+    }
+    /// Like `get_dependents`, without copying them.
+    pub fn dependents(&self, key: &K) -> &[K] {
+        match self.props.get(key) {
+            Some(p) => &p.dependents[..],
+            None => &[]
+        }
+    }
     pub fn iter_nonzero<'a>(&'a self) -> Box<Iterator<Item=&'a K> + 'a> {
         Box::new(self.props.iter().filter_map(|(k, v)| {
             if v.counter > 0 {
@@ -97,7 +184,7 @@ impl<K: Eq + Hash + Clone> InverseDependenciesCounter<K> {
     }
     pub fn change_counter_recursively(&mut self, key: K, diff: i32, change: &mut ChangedNonZero<K>) {
         let dependents = {
-            let p = self.props.entry(key.clone()).or_insert(InvProp::new());
+            let p = self.prop_mut(&key);
             let was_nonzero = p.counter > 0;
             p.counter += diff;
             let is_nonzero = p.counter > 0;
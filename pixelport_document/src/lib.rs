@@ -15,15 +15,25 @@ extern crate serde_json;
 pub mod hashmap_macro;
 #[macro_use]
 pub mod pon;
+pub mod property_key;
+pub mod pon_diagnostic;
+pub mod pon_json;
+pub mod pon_visitor;
 #[macro_use]
 pub mod pon_doc;
 #[macro_use]
 pub mod pon_translater;
+#[macro_use]
+pub mod from_pon;
 pub mod document;
+pub mod validation;
+pub mod dependency_graph;
+pub mod observers;
 pub mod selector;
 pub mod selection;
 pub mod entity_match;
 mod inverse_dependencies_counter;
+mod history;
 pub mod bus;
 pub mod topic;
 pub mod channel;
@@ -31,11 +41,20 @@ pub mod document_channels;
 mod doc_stream;
 
 pub use pon::*;
+pub use property_key::*;
+pub use pon_diagnostic::*;
+pub use pon_json::*;
+pub use pon_visitor::*;
 #[macro_use]
 pub use pon_doc::*;
 #[macro_use]
 pub use pon_translater::*;
+#[macro_use]
+pub use from_pon::*;
 pub use document::*;
+pub use validation::*;
+pub use dependency_graph::*;
+pub use observers::*;
 pub use selector::*;
 pub use selection::*;
 pub use entity_match::*;
@@ -0,0 +1,192 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::{channel, Sender, Receiver};
+
+use regex::Regex;
+
+use pon::*;
+use bus::*;
+use document::*;
+use selector::*;
+
+// Observers are notified of property values that changed, when the cycle is closed. Only the
+// values of observed properties that were invalidated during the cycle are evaluated. Observers
+// of many properties don't evaluate them when they're added, so their first change may come
+// without an old value.
+
+pub type ObserverId = u64;
+
+#[derive(Debug, Clone)]
+pub enum ObserverTarget {
+    Property(PropRef),
+    /// Properties of any entity with a key matching the pattern.
+    KeyPattern(Regex),
+    /// Properties of the entities matching the selector, optionally only those with a key
+    /// matching `key_pattern`.
+    Selector { selector: Selector, key_pattern: Option<Regex> }
+}
+impl ObserverTarget {
+    fn matches(&self, doc: &Document, root: EntityId, prop_ref: &PropRef) -> bool {
+        match self {
+            &ObserverTarget::Property(ref target) => target == prop_ref,
+            &ObserverTarget::KeyPattern(ref key_pattern) => key_pattern.is_match(&prop_ref.property_key),
+            &ObserverTarget::Selector { ref selector, ref key_pattern } => {
+                let key_matches = match key_pattern {
+                    &Some(ref key_pattern) => key_pattern.is_match(&prop_ref.property_key),
+                    &None => true
+                };
+                key_matches && doc.get_parent(prop_ref.entity_id).is_ok() && selector.matches(doc, root, prop_ref.entity_id)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PropertyChange {
+    pub prop_ref: PropRef,
+    /// None if the property didn't exist or couldn't be evaluated. For observers of a key
+    /// pattern or selector, also None if the property wasn't evaluated before the observer was
+    /// added.
+    pub old_value: Option<Box<BusValue>>,
+    pub new_value: Option<Box<BusValue>>
+}
+
+enum ObserverSink {
+    Callback(Box<Fn(&PropertyChange)>),
+    Channel(Sender<PropertyChange>)
+}
+
+struct Observer {
+    target: ObserverTarget,
+    sink: ObserverSink,
+    // The last values reported, or known when the observer was added
+    values: HashMap<PropRef, Option<Box<BusValue>>>
+}
+impl Observer {
+    // False if the observer is gone, which is the case when the receiving end of its channel
+    // was dropped
+    fn notify(&self, change: &PropertyChange) -> bool {
+        match &self.sink {
+            &ObserverSink::Callback(ref callback) => {
+                callback(change);
+                true
+            },
+            &ObserverSink::Channel(ref sender) => sender.send(change.clone()).is_ok()
+        }
+    }
+}
+
+pub struct Observers {
+    id_counter: ObserverId,
+    observers: HashMap<ObserverId, Observer>
+}
+
+impl Observers {
+    pub fn new() -> Observers {
+        Observers {
+            id_counter: 0,
+            observers: HashMap::new()
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.observers.len()
+    }
+    pub fn add_callback<F: Fn(&PropertyChange) + 'static>(&mut self, doc: &Document, target: ObserverTarget, callback: F) -> ObserverId {
+        self.add(doc, target, ObserverSink::Callback(Box::new(callback)))
+    }
+    pub fn add_channel(&mut self, doc: &Document, target: ObserverTarget) -> (ObserverId, Receiver<PropertyChange>) {
+        let (sender, receiver) = channel();
+        (self.add(doc, target, ObserverSink::Channel(sender)), receiver)
+    }
+    fn add(&mut self, doc: &Document, target: ObserverTarget, sink: ObserverSink) -> ObserverId {
+        let mut values = HashMap::new();
+        match &target {
+            &ObserverTarget::Property(ref prop_ref) => {
+                if doc.bus.has(prop_ref) {
+                    values.insert(prop_ref.clone(), evaluate(doc, prop_ref));
+                }
+            },
+            // Only the values that are known without evaluating anything
+            _ => if let Some(root) = doc.get_root() {
+                for prop_ref in doc.bus.iter() {
+                    if target.matches(doc, root, prop_ref) {
+                        values.insert(prop_ref.clone(), doc.bus.get_cached(prop_ref));
+                    }
+                }
+            }
+        }
+        self.id_counter += 1;
+        self.observers.insert(self.id_counter, Observer {
+            target: target,
+            sink: sink,
+            values: values
+        });
+        self.id_counter
+    }
+    pub fn remove(&mut self, id: ObserverId) -> bool {
+        self.observers.remove(&id).is_some()
+    }
+    pub fn on_cycle(&mut self, doc: &Document, changes: &CycleChanges) {
+        let root = doc.get_root();
+        let mut invalidated: HashSet<PropRef> = doc.bus.iter_invalidated().cloned().collect();
+        for change in &changes.invalidations_log {
+            invalidated.extend(change.added.iter().cloned());
+        }
+        let mut new_values: HashMap<PropRef, Option<Box<BusValue>>> = HashMap::new();
+        let mut gone = vec![];
+        for (id, observer) in self.observers.iter_mut() {
+            let mut prop_refs: Vec<PropRef> = match root {
+                Some(root) => invalidated.iter()
+                    .filter(|prop_ref| observer.target.matches(doc, root, prop_ref))
+                    .cloned().collect(),
+                None => vec![]
+            };
+            // Properties can be removed without being invalidated
+            prop_refs.extend(observer.values.keys().filter(|prop_ref| !doc.bus.has(prop_ref)).cloned());
+            prop_refs.sort();
+            prop_refs.dedup();
+            for prop_ref in prop_refs {
+                let new_value = new_values.entry(prop_ref.clone()).or_insert_with(|| evaluate(doc, &prop_ref)).clone();
+                let old_value = observer.values.remove(&prop_ref).unwrap_or(None);
+                if old_value == new_value {
+                    observer.values.insert(prop_ref, old_value);
+                    continue;
+                }
+                if new_value.is_some() {
+                    observer.values.insert(prop_ref.clone(), new_value.clone());
+                }
+                let change = PropertyChange { prop_ref: prop_ref, old_value: old_value, new_value: new_value };
+                if !observer.notify(&change) {
+                    gone.push(*id);
+                    break;
+                }
+            }
+        }
+        for id in gone {
+            self.observers.remove(&id);
+        }
+        // The removed properties have been reported above, so now the observers of single
+        // properties of removed entities are done
+        let removed: HashSet<EntityId> = changes.entities_removed.iter()
+            .map(|entity| entity.id)
+            .filter(|id| doc.get_parent(*id).is_err())
+            .collect();
+        if removed.len() > 0 {
+            self.observers = self.observers.drain().filter(|&(_, ref observer)| match &observer.target {
+                &ObserverTarget::Property(ref prop_ref) => !removed.contains(&prop_ref.entity_id),
+                _ => true
+            }).collect();
+            for observer in self.observers.values_mut() {
+                let stale: Vec<PropRef> = observer.values.keys()
+                    .filter(|prop_ref| removed.contains(&prop_ref.entity_id))
+                    .cloned().collect();
+                for prop_ref in stale {
+                    observer.values.remove(&prop_ref);
+                }
+            }
+        }
+    }
+}
+
+fn evaluate(doc: &Document, prop_ref: &PropRef) -> Option<Box<BusValue>> {
+    doc.bus.get(prop_ref, &doc.translater).ok()
+}
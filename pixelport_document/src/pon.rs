@@ -4,42 +4,73 @@ pub use pon::pon_peg::ParseError as PonParseError;
 
 use selector::*;
 use entity_match::*;
+use pon_diagnostic::*;
 use document::{Document, DocError, EntityId};
+use property_key::*;
 
 use std::collections::HashMap;
 use std::slice::SliceConcatExt;
 use std::hash::Hasher;
 use std::hash::Hash;
 use std::cmp::Eq;
+use std::mem;
 use cgmath::{Vector2, Vector3, Vector4, Matrix4};
 
-pub fn selector_from_string(string: &str) -> Result<Selector, PonParseError> {
-    pon_peg::selector(string)
+pub fn selector_from_string(string: &str) -> Result<Selector, PonDiagnostic> {
+    pon_peg::selector(string).map_err(|err| PonDiagnostic::from_parse_error(string, err))
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// A step into the value of a property: a field of an object or vector, or an element of an
+/// array.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PropPathSegment {
+    Field(String),
+    Index(usize)
+}
+impl ToString for PropPathSegment {
+    fn to_string(&self) -> String {
+        match self {
+            &PropPathSegment::Field(ref field) => format!(".{}", stringify_key(field)),
+            &PropPathSegment::Index(index) => format!("[{}]", index)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct NamedPropRef {
     pub selector: Selector,
-    pub property_key: String
+    pub property_key: String,
+    /// Path into the value of the property, e.g. `.x` in `@root.camera_pos.x`. Dependencies are
+    /// always on the whole property.
+    pub path: Vec<PropPathSegment>
 }
 impl NamedPropRef {
     pub fn new(selector: Selector, property_key: &str) -> NamedPropRef {
         NamedPropRef {
             selector: selector,
-            property_key: property_key.to_string()
+            property_key: property_key.to_string(),
+            path: vec![]
+        }
+    }
+    pub fn with_path(selector: Selector, property_key: &str, path: Vec<PropPathSegment>) -> NamedPropRef {
+        NamedPropRef {
+            selector: selector,
+            property_key: property_key.to_string(),
+            path: path
         }
     }
-    pub fn from_string(string: &str) -> Result<NamedPropRef, PonParseError> {
-        pon_peg::propref(string)
+    pub fn from_string(string: &str) -> Result<NamedPropRef, PonDiagnostic> {
+        pon_peg::propref(string).map_err(|err| PonDiagnostic::from_parse_error(string, err))
     }
     pub fn resolve(&self, document: &Document, start_entity_id: EntityId) -> Result<PropRef, DocError> {
         let entity_id = try!(self.selector.find_first(document, start_entity_id));
-        Ok(PropRef { entity_id: entity_id, property_key: self.property_key.clone() })
+        Ok(PropRef::new(entity_id, &self.property_key))
     }
 }
 impl ToString for NamedPropRef {
     fn to_string(&self) -> String {
-        format!("{}.{}", self.selector.to_string(), self.property_key)
+        let path: Vec<String> = self.path.iter().map(|segment| segment.to_string()).collect();
+        format!("{}.{}{}", self.selector.to_string(), self.property_key, path.concat())
     }
 }
 impl ToPon for NamedPropRef {
@@ -48,43 +79,152 @@ impl ToPon for NamedPropRef {
     }
 }
 
-#[derive(PartialEq, Debug, Clone, Hash, PartialOrd, Ord)]
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash, PartialOrd, Ord)]
 pub struct PropRef {
     pub entity_id: EntityId,
-    pub property_key: String
+    pub property_key: PropertyKey
 }
 impl PropRef {
+    /// Interns `property_key`, which only allocates the first time a key is seen.
     pub fn new(entity_id: EntityId, property_key: &str) -> PropRef {
         PropRef {
             entity_id: entity_id,
-            property_key: property_key.to_string()
+            property_key: PropertyKey::new(property_key)
         }
     }
 }
-impl Eq for PropRef {
-    // hack, relies on PartialEq to be defined
-}
-
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct PonCall {
     pub function_name: String,
     pub arg: Pon
 }
 impl PonCall {
+    // The operator this call is printed with, if its argument has a shape the operator syntax can express.
+    fn operator(&self) -> Option<InfixOperator> {
+        match infix_operator(&self.function_name) {
+            Some(operator) => {
+                let printable = match (operator.fixity, &self.arg) {
+                    // `-5` parses as a number literal, so negated literals are printed as calls
+                    (Fixity::Prefix, &Pon::Number(_)) | (Fixity::Prefix, &Pon::Integer(_)) => self.function_name != "neg",
+                    (Fixity::Prefix, _) => true,
+                    (Fixity::Flat, &Pon::Array(ref operands)) => operands.len() >= 2,
+                    (_, &Pon::Array(ref operands)) => operands.len() == 2,
+                    _ => false
+                };
+                if printable { Some(operator) } else { None }
+            },
+            None => None
+        }
+    }
+    fn precedence(&self) -> u8 {
+        match self.operator() {
+            Some(operator) => operator.precedence,
+            None => PRECEDENCE_PRIMARY
+        }
+    }
     fn stringify(&self, options: &PonStringifyOptions) -> String {
-        format!("{} {}", self.function_name.to_string(), self.arg.to_string())
+        match (self.operator(), &self.arg) {
+            (Some(operator), arg) if operator.fixity == Fixity::Prefix =>
+                format!("{}{}", operator.symbol, arg.stringify_operand(options, operator.precedence)),
+            (Some(operator), &Pon::Array(ref operands)) => {
+                let last = operands.len() - 1;
+                let strings: Vec<String> = operands.iter().enumerate().map(|(i, operand)| {
+                    // Operands binding as tightly as the operator itself only go without parentheses where
+                    // parsing would nest them the same way again
+                    let min_precedence = match operator.fixity {
+                        Fixity::Flat if i == 0 => match operand {
+                            &Pon::Call(box PonCall { ref function_name, .. }) if *function_name == self.function_name => operator.precedence + 1,
+                            _ => operator.precedence
+                        },
+                        Fixity::Left if i == 0 => operator.precedence,
+                        _ => operator.precedence + 1
+                    };
+                    match operand {
+                        // `a / b` would be read as a selector
+                        &Pon::Selector(_) if i < last && operator.symbol == "/" => format!("({})", operand.stringify(options)),
+                        _ => operand.stringify_operand(options, min_precedence)
+                    }
+                }).collect();
+                strings.join(&format!(" {} ", operator.symbol))
+            },
+            _ => format!("{} {}", self.function_name, self.arg.stringify_operand(options, PRECEDENCE_PRIMARY))
+        }
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Fixity {
+    Prefix,
+    // Chains of the operator are collected into one call, `a + b + c` is `add [a, b, c]`
+    Flat,
+    Left,
+    NonAssociative
+}
+
+#[derive(Debug, Clone, Copy)]
+struct InfixOperator {
+    symbol: &'static str,
+    precedence: u8,
+    fixity: Fixity
+}
+
+const PRECEDENCE_PRIMARY: u8 = 7;
+
+fn infix_operator(function_name: &str) -> Option<InfixOperator> {
+    let (symbol, precedence, fixity) = match function_name {
+        "or" => ("||", 1, Fixity::Flat),
+        "and" => ("&&", 2, Fixity::Flat),
+        "eq" => ("==", 3, Fixity::NonAssociative),
+        "neq" => ("!=", 3, Fixity::NonAssociative),
+        "lt" => ("<", 3, Fixity::NonAssociative),
+        "lte" => ("<=", 3, Fixity::NonAssociative),
+        "gt" => (">", 3, Fixity::NonAssociative),
+        "gte" => (">=", 3, Fixity::NonAssociative),
+        "add" => ("+", 4, Fixity::Flat),
+        "sub" => ("-", 4, Fixity::Left),
+        "mul" => ("*", 5, Fixity::Flat),
+        "div" => ("/", 5, Fixity::Left),
+        "modulo" => ("%", 5, Fixity::Left),
+        "not" => ("!", 6, Fixity::Prefix),
+        "neg" => ("-", 6, Fixity::Prefix),
+        _ => return None
+    };
+    Some(InfixOperator { symbol: symbol, precedence: precedence, fixity: fixity })
+}
+
+// Builds the calls for a chain of operators of the same precedence, as parsed by the grammar.
+fn fold_infix(first: Pon, rest: Vec<(&'static str, Pon)>) -> Pon {
+    let mut acc = first;
+    let mut last_function_name = None;
+    for (function_name, operand) in rest {
+        let extends_chain = last_function_name == Some(function_name) &&
+            infix_operator(function_name).map(|operator| operator.fixity == Fixity::Flat).unwrap_or(false);
+        if extends_chain {
+            if let Pon::Call(box PonCall { arg: Pon::Array(ref mut operands), .. }) = acc {
+                operands.push(operand);
+            }
+        } else {
+            acc = Pon::call(function_name, Pon::Array(vec![acc, operand]));
+        }
+        last_function_name = Some(function_name);
+    }
+    acc
+}
+
+/// Numbers compare by `canonical_f32_bits`, so `NaN == NaN` and `0.0 == -0.0`. This keeps
+/// equality reflexive and consistent with `Hash`, which lets pons be used as map keys.
+#[derive(Debug, Clone)]
 pub enum Pon {
     Call(Box<PonCall>),
     DepPropRef(NamedPropRef, Option<PropRef>),
     PropRef(NamedPropRef),
+    /// `$name`, an argument in the body of a document defined function.
+    Argument(String),
     Selector(Selector),
     Array(Vec<Pon>),
     Object(HashMap<String, Pon>),
     Number(f32),
+    Integer(i64),
     String(String),
     Boolean(bool),
     Nil
@@ -92,41 +232,36 @@ pub enum Pon {
 
 
 impl Pon {
-    pub fn from_string(string: &str) -> Result<Pon, PonParseError> {
-        pon_peg::body(string)
+    pub fn from_string(string: &str) -> Result<Pon, PonDiagnostic> {
+        pon_peg::body(string).map_err(|err| PonDiagnostic::from_parse_error(string, err))
     }
     pub fn call(function_name: &str, arg: Pon) -> Pon {
         Pon::Call(Box::new(PonCall { function_name: function_name.to_string(), arg: arg }))
     }
-    pub fn build_dependencies_array(&self, references: &mut Vec<PropRef>) {
+    /// Stringifies the pon so that it parses back as a single operand, i.e. as a call argument or
+    /// a selector match value. Operator expressions are parenthesized.
+    pub fn to_operand_string(&self) -> String {
+        self.stringify_operand(&PonStringifyOptions::default(), PRECEDENCE_PRIMARY)
+    }
+    fn precedence(&self) -> u8 {
         match self {
-            &Pon::Call(box PonCall { ref arg, .. } ) =>
-                arg.build_dependencies_array(references),
-            &Pon::DepPropRef(_, ref reference) => {
-                references.push(match reference {
-                    &Some(ref v) => v.clone(),
-                    &None => panic!("trying to run build_dependencies_array on non-resolved Pon")
-                });
-            },
-            &Pon::Object(ref hm) => {
-                for (_, v) in hm {
-                    v.build_dependencies_array(references);
-                }
-            },
-            &Pon::Array(ref arr) => {
-                for v in arr {
-                    v.build_dependencies_array(references);
-                }
-            },
-            _ => {}
+            &Pon::Call(box ref call) => call.precedence(),
+            _ => PRECEDENCE_PRIMARY
+        }
+    }
+    fn stringify_operand(&self, options: &PonStringifyOptions, min_precedence: u8) -> String {
+        if self.precedence() < min_precedence {
+            format!("({})", self.stringify(options))
+        } else {
+            self.stringify(options)
         }
     }
-
     fn stringify(&self, options: &PonStringifyOptions) -> String {
         match self {
             &Pon::Call(box ref typed_pon) => typed_pon.stringify(&options),
             &Pon::DepPropRef(ref named_prop_ref, ref resolved) => format!("@{}", named_prop_ref.to_string()),
             &Pon::PropRef(ref named_prop_ref) => format!("{}", named_prop_ref.to_string()),
+            &Pon::Argument(ref name) => format!("${}", name),
             &Pon::Selector(ref selector) => format!("{}", selector.to_string()),
             &Pon::Array(ref array) => {
                 let a: Vec<String> = array.iter().map(|x| x.stringify(&options)).collect();
@@ -136,20 +271,73 @@ impl Pon {
             },
             &Pon::Object(ref hm) => {
                 let mut a: Vec<String> = hm.iter()
-                    .map(|(k, v)| format!("{}: {}", k.to_string(), v.stringify(&options))).collect();
+                    .map(|(k, v)| format!("{}: {}", stringify_key(k), v.stringify(&options))).collect();
                 a.sort_by(|a, b| a.cmp(b));
                 let mut s = a.join(", ");
                 if options.break_up_lines && s.len() > 180 { s = a.join(",\n"); }
                 format!("{{ {} }}", s)
             },
-            &Pon::Number(ref v) => format!("{}", v),
-            &Pon::String(ref v) => format!("'{}'", v),
+            &Pon::Number(ref v) => stringify_number(*v),
+            &Pon::Integer(ref v) => format!("{}", v),
+            &Pon::String(ref v) => stringify_pon_string(v),
             &Pon::Boolean(ref v) => format!("{}", v),
             &Pon::Nil => "()".to_string()
         }
     }
 }
 
+fn is_identifier_char(c: char) -> bool {
+    match c {
+        'a'...'z' | 'A'...'Z' | '0'...'9' | '_' => true,
+        _ => false
+    }
+}
+
+pub fn is_pon_identifier(string: &str) -> bool {
+    match string.chars().next() {
+        None | Some('0'...'9') => false,
+        _ => string.chars().all(is_identifier_char)
+    }
+}
+
+// Numbers always get a decimal point (or are one of the non-finite literals), so that they don't
+// come back as integers when parsed.
+fn stringify_number(value: f32) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "inf".to_string() } else { "-inf".to_string() }
+    } else {
+        let s = format!("{}", value);
+        if s.contains('.') { s } else { s + ".0" }
+    }
+}
+
+pub fn stringify_pon_string(string: &str) -> String {
+    let mut s = "'".to_string();
+    for c in string.chars() {
+        match c {
+            '\\' => s.push_str("\\\\"),
+            '\'' => s.push_str("\\'"),
+            '\n' => s.push_str("\\n"),
+            '\r' => s.push_str("\\r"),
+            '\t' => s.push_str("\\t"),
+            c if c.is_control() => s.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => s.push(c)
+        }
+    }
+    s.push('\'');
+    s
+}
+
+fn stringify_key(key: &str) -> String {
+    if is_pon_identifier(key) {
+        key.to_string()
+    } else {
+        stringify_pon_string(key)
+    }
+}
+
 pub struct PonStringifyOptions {
     break_up_lines: bool
 }
@@ -167,10 +355,69 @@ impl ToString for Pon {
     }
 }
 
+/// The bits used when comparing and hashing floats: every NaN maps to the same bits
+/// and -0.0 maps to the bits of 0.0.
+pub fn canonical_f32_bits(value: f32) -> u32 {
+    if value.is_nan() {
+        0x7fc00000
+    } else if value == 0.0 {
+        0
+    } else {
+        unsafe { mem::transmute(value) }
+    }
+}
+
+impl PartialEq for Pon {
+    fn eq(&self, other: &Pon) -> bool {
+        match (self, other) {
+            (&Pon::Call(ref a), &Pon::Call(ref b)) => a == b,
+            (&Pon::DepPropRef(ref a, ref a_resolved), &Pon::DepPropRef(ref b, ref b_resolved)) => a == b && a_resolved == b_resolved,
+            (&Pon::PropRef(ref a), &Pon::PropRef(ref b)) => a == b,
+            (&Pon::Argument(ref a), &Pon::Argument(ref b)) => a == b,
+            (&Pon::Selector(ref a), &Pon::Selector(ref b)) => a == b,
+            (&Pon::Array(ref a), &Pon::Array(ref b)) => a == b,
+            (&Pon::Object(ref a), &Pon::Object(ref b)) => a == b,
+            (&Pon::Number(a), &Pon::Number(b)) => canonical_f32_bits(a) == canonical_f32_bits(b),
+            (&Pon::Integer(a), &Pon::Integer(b)) => a == b,
+            (&Pon::String(ref a), &Pon::String(ref b)) => a == b,
+            (&Pon::Boolean(a), &Pon::Boolean(b)) => a == b,
+            (&Pon::Nil, &Pon::Nil) => true,
+            _ => false
+        }
+    }
+}
+impl Eq for Pon {}
+
 impl Hash for Pon {
     fn hash<H>(&self, state: &mut H) where H: Hasher {
-        let str = format!("{:?}", self);
-        str.hash(state);
+        match self {
+            &Pon::Call(ref call) => { 0u8.hash(state); call.hash(state); },
+            &Pon::DepPropRef(ref named_prop_ref, ref resolved) => {
+                1u8.hash(state);
+                named_prop_ref.hash(state);
+                resolved.hash(state);
+            },
+            &Pon::PropRef(ref named_prop_ref) => { 2u8.hash(state); named_prop_ref.hash(state); },
+            &Pon::Argument(ref name) => { 11u8.hash(state); name.hash(state); },
+            &Pon::Selector(ref selector) => { 3u8.hash(state); selector.hash(state); },
+            &Pon::Array(ref array) => { 4u8.hash(state); array.hash(state); },
+            &Pon::Object(ref hm) => {
+                // HashMap iteration order is arbitrary, so hash the entries sorted by key
+                5u8.hash(state);
+                let mut keys: Vec<&String> = hm.keys().collect();
+                keys.sort();
+                keys.len().hash(state);
+                for key in keys {
+                    key.hash(state);
+                    hm[key].hash(state);
+                }
+            },
+            &Pon::Number(v) => { 6u8.hash(state); canonical_f32_bits(v).hash(state); },
+            &Pon::Integer(v) => { 7u8.hash(state); v.hash(state); },
+            &Pon::String(ref s) => { 8u8.hash(state); s.hash(state); },
+            &Pon::Boolean(v) => { 9u8.hash(state); v.hash(state); },
+            &Pon::Nil => 10u8.hash(state)
+        }
     }
 }
 
@@ -205,12 +452,23 @@ impl ToPon for f32 {
 }
 impl ToPon for u8 {
     fn to_pon(&self) -> Pon {
-        Pon::Number(*self as f32)
+        Pon::Integer(*self as i64)
     }
 }
+impl ToPon for i64 {
+    fn to_pon(&self) -> Pon {
+        Pon::Integer(*self)
+    }
+}
+// Pon integers are i64, so larger values are written as a placeholder that fails to translate
+// instead of wrapping around
 impl ToPon for u64 {
     fn to_pon(&self) -> Pon {
-        Pon::Number(*self as f32)
+        if *self <= ::std::i64::MAX as u64 {
+            Pon::Integer(*self as i64)
+        } else {
+            ::pon_translater::native_value_placeholder("error", &format!("{} doesn't fit in a Pon integer", self))
+        }
     }
 }
 impl ToPon for String {
@@ -0,0 +1,68 @@
+use pon::PonParseError;
+
+/// A parse error with enough context to point at the offending part of the source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PonDiagnostic {
+    pub source: String,
+    /// Byte range of the offending input; empty when the input ended unexpectedly.
+    pub span_start: usize,
+    pub span_end: usize,
+    /// 1-based line and column (in chars) of `span_start`.
+    pub line: usize,
+    pub column: usize,
+    /// The tokens the parser would have accepted, sorted.
+    pub expected: Vec<String>
+}
+
+impl PonDiagnostic {
+    pub fn from_parse_error(source: &str, error: PonParseError) -> PonDiagnostic {
+        let span_start = error.offset;
+        let span_end = match source[span_start..].chars().next() {
+            Some(c) => span_start + c.len_utf8(),
+            None => span_start
+        };
+        let mut expected: Vec<String> = error.expected.iter().map(|x| x.to_string()).collect();
+        expected.sort();
+        PonDiagnostic {
+            source: source.to_string(),
+            span_start: span_start,
+            span_end: span_end,
+            line: error.line,
+            column: error.column,
+            expected: expected
+        }
+    }
+    /// The text covered by the span, or an empty string at end of input.
+    pub fn found(&self) -> &str {
+        &self.source[self.span_start..self.span_end]
+    }
+    pub fn message(&self) -> String {
+        let found = if self.span_start == self.span_end {
+            "end of input".to_string()
+        } else {
+            format!("{:?}", self.found())
+        };
+        match self.expected.len() {
+            0 => format!("Unexpected {}", found),
+            1 => format!("Unexpected {}, expected {}", found, self.expected[0]),
+            _ => format!("Unexpected {}, expected one of {}", found, self.expected.join(", "))
+        }
+    }
+    /// Renders the message followed by the offending source line with a caret under the error.
+    pub fn render(&self) -> String {
+        let source_line = self.source.split('\n').nth(self.line - 1).unwrap_or("");
+        let gutter = self.line.to_string();
+        let padding: String = gutter.chars().map(|_| ' ').collect();
+        let indent: String = source_line.chars().take(self.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+        format!("error: {}\n{} --> line {}, column {}\n{} |\n{} | {}\n{} | {}^\n",
+            self.message(), padding, self.line, self.column,
+            padding, gutter, source_line, padding, indent)
+    }
+}
+
+impl ToString for PonDiagnostic {
+    fn to_string(&self) -> String {
+        format!("{} at line {}, column {}", self.message(), self.line, self.column)
+    }
+}
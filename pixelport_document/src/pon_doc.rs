@@ -141,10 +141,25 @@ pub struct PonDocFunction {
     pub name: String,
     pub target_type_name: String,
     pub arg: PonDocMatcher,
-    pub doc: String
+    pub doc: String,
+    /// False for functions that can return a different value for the same argument, like
+    /// `random_float`. Properties calling them are volatile.
+    pub pure: bool
 }
 
 impl PonDocFunction {
+    /// A pure function, see `pure`.
+    pub fn new(category: &str, module: &str, name: &str, target_type_name: &str, arg: PonDocMatcher, doc: &str) -> PonDocFunction {
+        PonDocFunction {
+            category: category.to_string(),
+            module: module.to_string(),
+            name: name.to_string(),
+            target_type_name: target_type_name.to_string(),
+            arg: arg,
+            doc: doc.to_string(),
+            pure: true
+        }
+    }
     pub fn generate_json(&self) -> serde_json::value::Value {
         let mut map = BTreeMap::new();
         map.insert("category".to_string(), serde_json::value::Value::String(self.category.to_string()));
@@ -152,6 +167,7 @@ impl PonDocFunction {
         map.insert("name".to_string(), serde_json::value::Value::String(self.name.to_string()));
         map.insert("target_type_name".to_string(), serde_json::value::Value::String(self.target_type_name.to_string()));
         map.insert("doc".to_string(), serde_json::value::Value::String(self.doc.to_string()));
+        map.insert("pure".to_string(), serde_json::value::Value::Bool(self.pure));
         let usage = format!(r#"{name} {arg_usage}"#, name=self.name,
             arg_usage=self.arg.generate_usage(0));
         map.insert("usage".to_string(), serde_json::value::Value::String(usage));
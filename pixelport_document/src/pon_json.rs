@@ -0,0 +1,243 @@
+use serde_json;
+use serde_json::value::Value;
+
+use pon::*;
+use selector::*;
+use pon_diagnostic::*;
+
+use std::collections::{HashMap, BTreeMap};
+
+// Pon values that have no JSON counterpart are encoded as objects tagged with a `$` prefixed key:
+//
+//   { "$call": "vec3", "arg": { "x": 1 } }
+//   { "$selector": "root:[name=x]" }
+//   { "$propref": "this.x" }
+//   { "$arg": "suit" }
+//   { "$dep": "this.x", "resolved": { "entity_id": 5, "property_key": "x" } }
+//   { "$number": "NaN" }                 (also "inf" and "-inf")
+//   { "$object": { "$key": 5 } }         (a plain object that has `$` prefixed keys)
+//
+// Any object with a `$` prefixed key is tagged, everything else maps one to one.
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum PonJsonError {
+    InvalidJson(String),
+    UnknownTag(String),
+    MultipleTags(Vec<String>),
+    MissingField { tag: String, field: String },
+    InvalidField { tag: String, field: String, expected: String },
+    InvalidSyntax { tag: String, diagnostic: PonDiagnostic }
+}
+impl ToString for PonJsonError {
+    fn to_string(&self) -> String {
+        match self {
+            &PonJsonError::InvalidJson(ref message) => format!("Invalid json: {}", message),
+            &PonJsonError::UnknownTag(ref tag) => format!("Unknown tag {}", tag),
+            &PonJsonError::MultipleTags(ref tags) => format!("Object has more than one tag: {}", tags.join(", ")),
+            &PonJsonError::MissingField { ref tag, ref field } => format!("{} is missing field {}", tag, field),
+            &PonJsonError::InvalidField { ref tag, ref field, ref expected } => format!("Expected {}.{} to be {}", tag, field, expected),
+            &PonJsonError::InvalidSyntax { ref tag, ref diagnostic } => format!("Invalid {}: {}", tag, diagnostic.to_string()),
+        }
+    }
+}
+
+fn is_tag(key: &str) -> bool {
+    key.starts_with("$")
+}
+
+fn tagged(tag: &str, value: Value) -> Value {
+    let mut map = BTreeMap::new();
+    map.insert(tag.to_string(), value);
+    Value::Object(map)
+}
+
+fn string_field(tag: &str, field: &str, map: &BTreeMap<String, Value>) -> Result<String, PonJsonError> {
+    match map.get(field) {
+        Some(&Value::String(ref s)) => Ok(s.clone()),
+        Some(_) => Err(PonJsonError::InvalidField { tag: tag.to_string(), field: field.to_string(), expected: "a string".to_string() }),
+        None => Err(PonJsonError::MissingField { tag: tag.to_string(), field: field.to_string() })
+    }
+}
+
+fn parse_propref(tag: &str, string: &str) -> Result<NamedPropRef, PonJsonError> {
+    NamedPropRef::from_string(string)
+        .map_err(|diagnostic| PonJsonError::InvalidSyntax { tag: tag.to_string(), diagnostic: diagnostic })
+}
+
+fn prop_ref_to_json(prop_ref: &PropRef) -> Value {
+    let mut map = BTreeMap::new();
+    map.insert("entity_id".to_string(), Value::U64(prop_ref.entity_id));
+    map.insert("property_key".to_string(), Value::String(prop_ref.property_key.to_string()));
+    Value::Object(map)
+}
+
+fn prop_ref_from_json(value: &Value) -> Result<PropRef, PonJsonError> {
+    let invalid = || PonJsonError::InvalidField { tag: "$dep".to_string(), field: "resolved".to_string(),
+        expected: "an object with entity_id and property_key".to_string() };
+    match value {
+        &Value::Object(ref map) => {
+            let entity_id = match map.get("entity_id") {
+                Some(&Value::U64(v)) => v,
+                Some(&Value::I64(v)) if v >= 0 => v as u64,
+                _ => return Err(invalid())
+            };
+            match map.get("property_key") {
+                Some(&Value::String(ref key)) => Ok(PropRef::new(entity_id, key)),
+                _ => Err(invalid())
+            }
+        },
+        _ => Err(invalid())
+    }
+}
+
+impl Pon {
+    pub fn to_json(&self) -> Value {
+        match self {
+            &Pon::Call(box PonCall { ref function_name, ref arg }) => {
+                let mut map = BTreeMap::new();
+                map.insert("$call".to_string(), Value::String(function_name.clone()));
+                if *arg != Pon::Nil {
+                    map.insert("arg".to_string(), arg.to_json());
+                }
+                Value::Object(map)
+            },
+            &Pon::DepPropRef(ref named_prop_ref, ref resolved) => {
+                let mut map = BTreeMap::new();
+                map.insert("$dep".to_string(), Value::String(named_prop_ref.to_string()));
+                if let &Some(ref prop_ref) = resolved {
+                    map.insert("resolved".to_string(), prop_ref_to_json(prop_ref));
+                }
+                Value::Object(map)
+            },
+            &Pon::PropRef(ref named_prop_ref) => tagged("$propref", Value::String(named_prop_ref.to_string())),
+            &Pon::Argument(ref name) => tagged("$arg", Value::String(name.clone())),
+            &Pon::Selector(ref selector) => tagged("$selector", Value::String(selector.to_string())),
+            &Pon::Array(ref array) => Value::Array(array.iter().map(|v| v.to_json()).collect()),
+            &Pon::Object(ref hm) => {
+                let map: BTreeMap<String, Value> = hm.iter().map(|(k, v)| (k.clone(), v.to_json())).collect();
+                if hm.keys().any(|k| is_tag(k)) {
+                    tagged("$object", Value::Object(map))
+                } else {
+                    Value::Object(map)
+                }
+            },
+            &Pon::Number(v) => {
+                if v.is_finite() {
+                    Value::F64(v as f64)
+                } else {
+                    tagged("$number", Value::String(Pon::Number(v).to_string()))
+                }
+            },
+            // serde_json reads non-negative integers as U64, so they're written that way as well
+            &Pon::Integer(v) if v >= 0 => Value::U64(v as u64),
+            &Pon::Integer(v) => Value::I64(v),
+            &Pon::String(ref s) => Value::String(s.clone()),
+            &Pon::Boolean(v) => Value::Bool(v),
+            &Pon::Nil => Value::Null
+        }
+    }
+    pub fn from_json(value: &Value) -> Result<Pon, PonJsonError> {
+        match value {
+            &Value::Null => Ok(Pon::Nil),
+            &Value::Bool(v) => Ok(Pon::Boolean(v)),
+            &Value::I64(v) => Ok(Pon::Integer(v)),
+            &Value::U64(v) => {
+                if v <= ::std::i64::MAX as u64 {
+                    Ok(Pon::Integer(v as i64))
+                } else {
+                    Ok(Pon::Number(v as f32))
+                }
+            },
+            &Value::F64(v) => Ok(Pon::Number(v as f32)),
+            &Value::String(ref s) => Ok(Pon::String(s.clone())),
+            &Value::Array(ref array) => {
+                let mut pons = vec![];
+                for v in array {
+                    pons.push(try!(Pon::from_json(v)));
+                }
+                Ok(Pon::Array(pons))
+            },
+            &Value::Object(ref map) => {
+                let tags: Vec<String> = map.keys().filter(|k| is_tag(k)).cloned().collect();
+                match tags.len() {
+                    0 => object_from_json(map),
+                    1 => tagged_from_json(&tags[0], map),
+                    _ => Err(PonJsonError::MultipleTags(tags))
+                }
+            }
+        }
+    }
+    pub fn to_json_string(&self) -> String {
+        serde_json::to_string(&self.to_json()).unwrap()
+    }
+    pub fn from_json_string(string: &str) -> Result<Pon, PonJsonError> {
+        match serde_json::from_str::<Value>(string) {
+            Ok(value) => Pon::from_json(&value),
+            Err(err) => Err(PonJsonError::InvalidJson(err.to_string()))
+        }
+    }
+}
+
+fn object_from_json(map: &BTreeMap<String, Value>) -> Result<Pon, PonJsonError> {
+    let mut hm = HashMap::new();
+    for (k, v) in map {
+        hm.insert(k.clone(), try!(Pon::from_json(v)));
+    }
+    Ok(Pon::Object(hm))
+}
+
+fn tagged_from_json(tag: &str, map: &BTreeMap<String, Value>) -> Result<Pon, PonJsonError> {
+    match tag {
+        "$call" => {
+            let function_name = try!(string_field(tag, tag, map));
+            let arg = match map.get("arg") {
+                Some(arg) => try!(Pon::from_json(arg)),
+                None => Pon::Nil
+            };
+            Ok(Pon::call(&function_name, arg))
+        },
+        "$selector" => {
+            let string = try!(string_field(tag, tag, map));
+            match Selector::from_string(&string) {
+                Ok(selector) => Ok(Pon::Selector(selector)),
+                Err(diagnostic) => Err(PonJsonError::InvalidSyntax { tag: tag.to_string(), diagnostic: diagnostic })
+            }
+        },
+        "$propref" => {
+            let string = try!(string_field(tag, tag, map));
+            Ok(Pon::PropRef(try!(parse_propref(tag, &string))))
+        },
+        "$arg" => {
+            let name = try!(string_field(tag, tag, map));
+            if is_pon_identifier(&name) {
+                Ok(Pon::Argument(name))
+            } else {
+                Err(PonJsonError::InvalidField { tag: tag.to_string(), field: tag.to_string(), expected: "an identifier".to_string() })
+            }
+        },
+        "$dep" => {
+            let string = try!(string_field(tag, tag, map));
+            let resolved = match map.get("resolved") {
+                Some(&Value::Null) | None => None,
+                Some(resolved) => Some(try!(prop_ref_from_json(resolved)))
+            };
+            Ok(Pon::DepPropRef(try!(parse_propref(tag, &string)), resolved))
+        },
+        "$number" => {
+            let string = try!(string_field(tag, tag, map));
+            match &string[..] {
+                "NaN" => Ok(Pon::Number(::std::f32::NAN)),
+                "inf" => Ok(Pon::Number(::std::f32::INFINITY)),
+                "-inf" => Ok(Pon::Number(::std::f32::NEG_INFINITY)),
+                _ => Err(PonJsonError::InvalidField { tag: tag.to_string(), field: tag.to_string(), expected: "NaN, inf or -inf".to_string() })
+            }
+        },
+        "$object" => {
+            match map.get(tag) {
+                Some(&Value::Object(ref inner)) => object_from_json(inner),
+                _ => Err(PonJsonError::InvalidField { tag: tag.to_string(), field: tag.to_string(), expected: "an object".to_string() })
+            }
+        },
+        _ => Err(PonJsonError::UnknownTag(tag.to_string()))
+    }
+}
@@ -1,10 +1,15 @@
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::any::TypeId;
 
 use pon::*;
 use bus::*;
 use pon_doc::*;
+use from_pon::*;
+use pon_visitor::*;
+use selector::*;
 use serde_json;
+use cgmath::{Vector2, Vector3, Vector4, Matrix4};
 
 
 #[macro_export]
@@ -84,9 +89,17 @@ macro_rules! pon_expand {
 }
 
 
+// Functions marked with `#[impure]` return a different value each time they're called, such
+// as `random_float`. Other attributes are rejected.
+#[macro_export]
+macro_rules! pon_function_is_pure {
+    () => (true);
+    (impure) => (false);
+}
+
 #[macro_export]
 macro_rules! pon_register_functions {
-    ($category:expr, $module:expr, $translater:expr => $($doc:expr, $func_name:ident($($args:tt)*) $ret:ty => $body:block)*) => ($({
+    ($category:expr, $module:expr, $translater:expr => $($doc:expr, $(#[$attr:ident])* $func_name:ident($($args:tt)*) $ret:ty => $body:block)*) => ($({
         fn $func_name(pon: &Pon, translater: &PonTranslater, bus: &$crate::bus::Bus) -> Result<Box<$crate::bus::BusValue>, PonTranslaterErr> {
             pon_expand!(pon, translater, bus => $($args)*);
             let val: Result<$ret, PonTranslaterErr> = $body;
@@ -101,7 +114,8 @@ macro_rules! pon_register_functions {
             name: stringify!($func_name).to_string(),
             target_type_name: stringify!($ret).to_string(),
             arg: pon_doc_expand!($($args)*),
-            doc: $doc.to_string()
+            doc: $doc.to_string(),
+            pure: pon_function_is_pure!($($attr)*)
         };
         $translater.register_function($func_name, doc);
     })*);
@@ -112,15 +126,77 @@ struct PonFn {
     doc: PonDocFunction
 }
 
+// A function defined in the document, evaluated by substituting the arguments into its body.
+#[derive(Clone)]
+struct DocumentFunction {
+    body: PropRef,
+    doc: PonDocFunction
+}
+
+// Converts a value of one type into another, see `register_coercion`.
+struct Coercion {
+    func: Box<Fn(&Box<BusValue>) -> Option<Box<BusValue>>>,
+    from_type_name: String,
+    to_type_name: String
+}
+
+// Writes a value of some type as Pon, see `register_to_pon`.
+type ToPonFn = Box<Fn(&Box<BusValue>) -> Option<Pon>>;
+
+/// The document functions defined at some point, see `PonTranslater::document_functions_snapshot`.
+pub struct DocumentFunctionsSnapshot(HashMap<String, DocumentFunction>);
+
 pub struct PonTranslater {
-    functions: HashMap<String, PonFn>
+    functions: HashMap<String, PonFn>,
+    document_functions: HashMap<String, DocumentFunction>,
+    // The body properties of `document_functions`
+    document_function_bodies: HashSet<PropRef>,
+    coercions: HashMap<(TypeId, TypeId), Coercion>,
+    to_pons: HashMap<TypeId, ToPonFn>
 }
 
 impl PonTranslater {
     pub fn new() -> PonTranslater {
-        PonTranslater {
-            functions: HashMap::new()
-        }
+        let mut translater = PonTranslater {
+            functions: HashMap::new(),
+            document_functions: HashMap::new(),
+            document_function_bodies: HashSet::new(),
+            coercions: HashMap::new(),
+            to_pons: HashMap::new()
+        };
+        translater.register_coercion(|string: &String| Selector::from_string(string).ok());
+        translater.register_to_pon::<()>();
+        translater.register_to_pon::<bool>();
+        translater.register_to_pon::<f32>();
+        translater.register_to_pon::<u8>();
+        translater.register_to_pon::<i64>();
+        translater.register_to_pon::<u64>();
+        translater.register_to_pon::<String>();
+        translater.register_to_pon::<Pon>();
+        // What arrays, objects and property references evaluate to
+        translater.register_to_pon::<Vec<Pon>>();
+        translater.register_to_pon::<HashMap<String, Pon>>();
+        translater.register_to_pon::<NamedPropRef>();
+        translater.register_to_pon::<Selector>();
+        translater.register_to_pon::<Vector2<f32>>();
+        translater.register_to_pon::<Vector3<f32>>();
+        translater.register_to_pon::<Vector4<f32>>();
+        translater.register_to_pon::<Matrix4<f32>>();
+        translater.register_to_pon::<Vec<f32>>();
+        translater.register_to_pon::<Vec<String>>();
+        translater.register_to_pon::<Vec<Matrix4<f32>>>();
+        translater.register_function(|arg: &Pon, _: &PonTranslater, _: &Bus| {
+            Err(PonTranslaterErr::Generic(format!("Can't create a native value from {}", arg.to_string())))
+        }, PonDocFunction {
+            category: "document".to_string(),
+            module: "Document".to_string(),
+            name: "native_value".to_string(),
+            target_type_name: "Pon".to_string(),
+            arg: pon_doc_expand!({ type_name: (String) optional, error: (String) optional, }),
+            doc: "Written in place of values that can't be written as Pon, such as values of types without a registered to_pon or that failed to evaluate. Always fails to translate.".to_string(),
+            pure: true
+        });
+        translater
     }
     pub fn register_function<F>(&mut self, func: F, doc: PonDocFunction)
         where F: Fn(&Pon, &PonTranslater, &Bus) -> Result<Box<BusValue>, PonTranslaterErr> + 'static {
@@ -129,9 +205,105 @@ impl PonTranslater {
             doc: doc
         });
     }
+    /// Registers `T::function_name()` as a function producing a `T`, see `pon_struct!`.
+    pub fn register_pon_call<T: FromPonCall + 'static>(&mut self, category: &str, module: &str, doc: &str) {
+        let doc = PonDocFunction {
+            category: category.to_string(),
+            module: module.to_string(),
+            name: T::function_name().to_string(),
+            target_type_name: T::type_name().to_string(),
+            arg: T::arg_pon_doc_matcher(),
+            doc: doc.to_string(),
+            pure: true
+        };
+        self.register_function(|arg: &Pon, translater: &PonTranslater, bus: &Bus| {
+            match T::from_pon_arg(arg, translater, bus) {
+                Ok(v) => Ok(Box::new(v) as Box<BusValue>),
+                Err(err) => Err(err)
+            }
+        }, doc);
+    }
+    /// Registers a conversion used by `translate` and typed property reads when a value of type `S`
+    /// is found where a `T` is expected. Returning None means the value can't be converted, for instance a fractional
+    /// number where an integer is expected. Replaces any earlier coercion between the same types.
+    pub fn register_coercion<S: BusValue, T: BusValue, F>(&mut self, func: F)
+        where F: Fn(&S) -> Option<T> + 'static {
+        let (from_type_name, to_type_name) = unsafe {
+            (::std::intrinsics::type_name::<S>(), ::std::intrinsics::type_name::<T>())
+        };
+        self.coercions.insert((TypeId::of::<S>(), TypeId::of::<T>()), Coercion {
+            func: Box::new(move |value: &Box<BusValue>| match (**value).downcast_ref::<S>() {
+                Some(v) => func(v).map(|v| Box::new(v) as Box<BusValue>),
+                None => None
+            }),
+            from_type_name: from_type_name.to_string(),
+            to_type_name: to_type_name.to_string()
+        });
+    }
+    /// Makes values of type `T` writable as Pon with `value_to_pon`, so that values set natively
+    /// by modules can be written back to PML and streamed.
+    pub fn register_to_pon<T: BusValue + ToPon>(&mut self) {
+        self.to_pons.insert(TypeId::of::<T>(), Box::new(|value: &Box<BusValue>| {
+            (**value).downcast_ref::<T>().map(|v| v.to_pon())
+        }));
+    }
+    /// Writes `value` as Pon. Values of types that aren't registered with `register_to_pon` are
+    /// written as `native_value { type_name: '...' }`.
+    pub fn value_to_pon(&self, value: &Box<BusValue>) -> Pon {
+        let pon = match self.to_pons.get(&(**value).bus_value_type_id()) {
+            Some(to_pon) => to_pon(value),
+            None => None
+        };
+        match pon {
+            Some(pon) => pon,
+            None => native_value_placeholder("type_name", (**value).bus_value_type_name())
+        }
+    }
+    /// The full type names of all registered coercions, as (from, to) pairs.
+    pub fn get_coercion_type_names(&self) -> Vec<(&str, &str)> {
+        self.coercions.values().map(|c| (&c.from_type_name[..], &c.to_type_name[..])).collect()
+    }
+    /// Defines a function whose body is the Pon expression of the `body` property. The body
+    /// refers to the fields of the argument as `$name`. Document functions shadow registered
+    /// functions with the same name.
+    pub fn define_document_function(&mut self, body: PropRef, doc: PonDocFunction) {
+        self.remove_document_function(&doc.name);
+        self.document_function_bodies.insert(body);
+        self.document_functions.insert(doc.name.to_string(), DocumentFunction {
+            body: body,
+            doc: doc
+        });
+    }
+    pub fn remove_document_function(&mut self, function_name: &str) {
+        if let Some(function) = self.document_functions.remove(function_name) {
+            self.document_function_bodies.remove(&function.body);
+        }
+    }
+    /// True if `prop_ref` is the body of a document function. Bodies aren't evaluated as
+    /// properties, since their arguments are only bound when the function is called.
+    pub fn is_document_function_body(&self, prop_ref: &PropRef) -> bool {
+        self.document_function_bodies.contains(prop_ref)
+    }
+    pub fn get_document_function_body(&self, function_name: &str) -> Option<&PropRef> {
+        self.document_functions.get(function_name).map(|f| &f.body)
+    }
+    pub fn document_functions_snapshot(&self) -> DocumentFunctionsSnapshot {
+        DocumentFunctionsSnapshot(self.document_functions.clone())
+    }
+    pub fn restore_document_functions(&mut self, snapshot: DocumentFunctionsSnapshot) {
+        self.document_functions = snapshot.0;
+        self.document_function_bodies = self.document_functions.values().map(|f| f.body).collect();
+    }
+    /// False if the expression calls an impure function, which means its value can change
+    /// without any of its dependencies changing.
+    pub fn is_pure(&self, pon: &Pon) -> bool {
+        let mut purity = Purity { translater: self, pure: true };
+        pon.visit(&mut purity);
+        purity.pure
+    }
     pub fn translate<T: BusValue>(&self, pon: &Pon, bus: &Bus) -> Result<T, PonTranslaterErr> {
-        match try!(self.translate_raw(pon, bus)).downcast::<T>() {
-            Ok(box v) => Ok(v),
+        match self.convert::<T>(try!(self.translate_raw(pon, bus))) {
+            Ok(v) => Ok(v),
             Err(_) => {
                 let to_type_name = unsafe {
                     ::std::intrinsics::type_name::<T>()
@@ -143,9 +315,31 @@ impl PonTranslater {
             }
         }
     }
+    /// Downcasts `value` to a `T`, converting between integers and numbers where that can be
+    /// done exactly, or else with a registered coercion. The value is given back if it can't be
+    /// converted. Used by both `translate` and `Bus::get_typed`.
+    pub fn convert<T: BusValue>(&self, value: Box<BusValue>) -> Result<T, Box<BusValue>> {
+        let value = match value.downcast::<T>() {
+            Ok(box v) => return Ok(v),
+            Err(value) => value
+        };
+        if let Some(v) = convert_number::<T>(&value) {
+            return Ok(v);
+        }
+        match self.coerce::<T>(&value) {
+            Some(v) => Ok(v),
+            None => Err(value)
+        }
+    }
     pub fn translate_raw(&self, pon: &Pon, bus: &Bus) -> Result<Box<BusValue>, PonTranslaterErr> {
         match pon {
             &Pon::Call(box PonCall { ref function_name, ref arg }) => {
+                if let Some(func) = self.document_functions.get(function_name) {
+                    return match self.call_document_function(func, arg, bus) {
+                        Ok(val) => Ok(val),
+                        Err(err) => Err(PonTranslaterErr::CallError { in_pon: pon.clone(), error: Box::new(err) })
+                    };
+                }
                 match self.functions.get(function_name) {
                     Some(func) => match (*func.func)(arg, self, bus) {
                         Ok(val) => Ok(val),
@@ -157,37 +351,212 @@ impl PonTranslater {
                 }
             },
             &Pon::DepPropRef(ref named_prop_ref, Some(ref prop_ref)) => {
-                match bus.get(&prop_ref, self) {
-                    Ok(val) => Ok(val),
-                    Err(err @ BusError::NoSuchEntry { .. }) => Err(PonTranslaterErr::BadDependency { property: named_prop_ref.clone(), error: Box::new(err) }),
-                    Err(err) => Err(PonTranslaterErr::BusError(Box::new(err)))
+                let mut value = match bus.get(&prop_ref, self) {
+                    Ok(val) => val,
+                    Err(err @ BusError::NoSuchEntry { .. }) => return Err(PonTranslaterErr::BadDependency { property: named_prop_ref.clone(), error: Box::new(err) }),
+                    Err(err) => return Err(PonTranslaterErr::BusError(Box::new(err)))
+                };
+                for segment in &named_prop_ref.path {
+                    value = match self.translate_path_segment(&value, segment, bus) {
+                        Some(result) => try!(result),
+                        None => return Err(PonTranslaterErr::InvalidPropPath { property: named_prop_ref.clone(), segment: segment.clone() })
+                    };
                 }
+                Ok(value)
             },
             &Pon::DepPropRef(ref named_prop_ref, None) => panic!("Trying to translate on non-resolved dependency reference"),
             &Pon::PropRef(ref named_prop_ref) => Ok(Box::new(named_prop_ref.clone())),
+            &Pon::Argument(ref name) => Err(PonTranslaterErr::UnboundArgument { name: name.clone() }),
             &Pon::Selector(ref selector) => Ok(Box::new(selector.clone())),
             &Pon::Array(ref value) => Ok(Box::new(value.clone())),
             &Pon::Object(ref value) => Ok(Box::new(value.clone())),
             &Pon::Number(ref value) => Ok(Box::new(value.clone())),
+            &Pon::Integer(ref value) => Ok(Box::new(value.clone())),
             &Pon::String(ref value) => Ok(Box::new(value.clone())),
             &Pon::Boolean(ref value) => Ok(Box::new(value.clone())),
             &Pon::Nil => Ok(Box::new(()))
         }
     }
+    fn coerce<T: BusValue>(&self, value: &Box<BusValue>) -> Option<T> {
+        let coercion = match self.coercions.get(&((**value).bus_value_type_id(), TypeId::of::<T>())) {
+            Some(coercion) => coercion,
+            None => return None
+        };
+        match (*coercion.func)(value) {
+            Some(converted) => match converted.downcast::<T>() {
+                Ok(box v) => Some(v),
+                Err(_) => None
+            },
+            None => None
+        }
+    }
+    fn call_document_function(&self, func: &DocumentFunction, arg: &Pon, bus: &Bus) -> Result<Box<BusValue>, PonTranslaterErr> {
+        let mut body = match bus.get_entry(&func.body) {
+            Some(&BusEntryValue::Pon { ref expression, .. }) => expression.clone(),
+            _ => return Err(PonTranslaterErr::BadDependency {
+                property: NamedPropRef::new(Selector::id(func.body.entity_id), &func.body.property_key),
+                error: Box::new(BusError::NoSuchEntry { prop_ref: func.body.clone() })
+            })
+        };
+        let args = match arg {
+            &Pon::Nil => HashMap::new(),
+            _ => try!(self.translate::<HashMap<String, Pon>>(arg, bus))
+        };
+        try!(body.fold(&mut ArgumentBinder { args: &args }));
+        self.translate_raw(&body, bus)
+    }
+    // None if the value doesn't have the field or element
+    fn translate_path_segment(&self, value: &Box<BusValue>, segment: &PropPathSegment, bus: &Bus) -> Option<Result<Box<BusValue>, PonTranslaterErr>> {
+        let pon = if let Some(hm) = (**value).downcast_ref::<HashMap<String, Pon>>() {
+            match segment {
+                &PropPathSegment::Field(ref field) => hm.get(field),
+                _ => None
+            }
+        } else if let Some(array) = (**value).downcast_ref::<Vec<Pon>>() {
+            match segment {
+                &PropPathSegment::Index(index) => array.get(index),
+                _ => None
+            }
+        } else if let Some(pon) = (**value).downcast_ref::<Pon>() {
+            match (pon, segment) {
+                (&Pon::Object(ref hm), &PropPathSegment::Field(ref field)) => hm.get(field),
+                (&Pon::Array(ref array), &PropPathSegment::Index(index)) => array.get(index),
+                _ => None
+            }
+        } else if let Some(v) = (**value).downcast_ref::<Vector2<f32>>() {
+            return vector_component(segment, &[v.x, v.y]).map(|v| Ok(Box::new(v) as Box<BusValue>));
+        } else if let Some(v) = (**value).downcast_ref::<Vector3<f32>>() {
+            return vector_component(segment, &[v.x, v.y, v.z]).map(|v| Ok(Box::new(v) as Box<BusValue>));
+        } else if let Some(v) = (**value).downcast_ref::<Vector4<f32>>() {
+            return vector_component(segment, &[v.x, v.y, v.z, v.w]).map(|v| Ok(Box::new(v) as Box<BusValue>));
+        } else if let Some(m) = (**value).downcast_ref::<Matrix4<f32>>() {
+            return vector_component(segment, &[m.x, m.y, m.z, m.w]).map(|v| Ok(Box::new(v) as Box<BusValue>));
+        } else {
+            None
+        };
+        pon.map(|pon| self.translate_raw(pon, bus))
+    }
+    pub fn get_doc_func(&self, function_name: &str) -> Option<&PonDocFunction> {
+        match self.document_functions.get(function_name) {
+            Some(func) => Some(&func.doc),
+            None => self.functions.get(function_name).map(|v| &v.doc)
+        }
+    }
     pub fn get_doc_funcs(&self) -> Vec<PonDocFunction> {
-        self.functions.values().map(|v| v.doc.clone()).collect()
+        self.doc_funcs_iter().cloned().collect()
     }
     pub fn generate_json_docs(&self) -> String {
-        let funcs: Vec<serde_json::value::Value> = self.functions.values()
-            .map(|v| v.doc.generate_json())
+        let funcs: Vec<serde_json::value::Value> = self.doc_funcs_iter()
+            .map(|doc| doc.generate_json())
             .collect();
         serde_json::to_string(&funcs).unwrap()
     }
+    fn doc_funcs_iter<'a>(&'a self) -> Box<Iterator<Item=&'a PonDocFunction> + 'a> {
+        let document_functions = &self.document_functions;
+        Box::new(document_functions.values().map(|f| &f.doc)
+            .chain(self.functions.iter().filter(move |&(name, _)| !document_functions.contains_key(name)).map(|(_, f)| &f.doc)))
+    }
+}
+
+struct Purity<'a> {
+    translater: &'a PonTranslater,
+    pure: bool
+}
+
+impl<'a> PonVisitor for Purity<'a> {
+    fn visit_call(&mut self, call: &PonCall) {
+        if let Some(doc) = self.translater.get_doc_func(&call.function_name) {
+            self.pure = self.pure && doc.pure;
+        }
+        walk_call(self, call);
+    }
+}
+
+// Replaces the `$name` arguments in a document function body with the fields of the argument.
+struct ArgumentBinder<'a> {
+    args: &'a HashMap<String, Pon>
+}
+
+impl<'a> PonFolder for ArgumentBinder<'a> {
+    type Error = PonTranslaterErr;
+    fn fold_pon(&mut self, pon: &mut Pon) -> Result<(), PonTranslaterErr> {
+        let name = match pon {
+            &mut Pon::Argument(ref name) => Some(name.clone()),
+            _ => None
+        };
+        match name {
+            Some(name) => match self.args.get(&name) {
+                Some(value) => {
+                    *pon = value.clone();
+                    Ok(())
+                },
+                None => Err(PonTranslaterErr::RequiredFieldMissing { field: name })
+            },
+            None => walk_pon_mut(self, pon)
+        }
+    }
+}
+
+// Components are named x, y, z and w, or indexed from 0. Matrix components are columns.
+fn vector_component<T: Clone>(segment: &PropPathSegment, components: &[T]) -> Option<T> {
+    let index = match segment {
+        &PropPathSegment::Field(ref field) => match &field[..] {
+            "x" => 0,
+            "y" => 1,
+            "z" => 2,
+            "w" => 3,
+            _ => return None
+        },
+        &PropPathSegment::Index(index) => index
+    };
+    components.get(index).cloned()
+}
+
+// 2^63, the smallest f32 that is out of range for an i64. Exactly representable as an f32.
+const I64_LIMIT: f32 = 9223372036854775808.0;
+
+// Integers and numbers can be used interchangeably as long as the value is represented exactly
+// in the target type, so that `5` can be used where an f32 is expected and `5.0` where an entity
+// id is expected. Integers too large to be exact as an f32, such as 16777217, aren't converted.
+fn convert_number<T: BusValue>(value: &Box<BusValue>) -> Option<T> {
+    let target = TypeId::of::<T>();
+    let converted: Box<BusValue> = if let Some(v) = (**value).downcast_ref::<i64>() {
+        if target == TypeId::of::<f32>() {
+            let f = *v as f32;
+            if f >= -I64_LIMIT && f < I64_LIMIT && f as i64 == *v {
+                Box::new(f)
+            } else {
+                return None;
+            }
+        } else if target == TypeId::of::<u64>() && *v >= 0 {
+            Box::new(*v as u64)
+        } else {
+            return None;
+        }
+    } else if let Some(v) = (**value).downcast_ref::<f32>() {
+        if v.fract() != 0.0 || !v.is_finite() {
+            return None;
+        } else if target == TypeId::of::<i64>() && *v >= -I64_LIMIT && *v < I64_LIMIT {
+            Box::new(*v as i64)
+        } else if target == TypeId::of::<u64>() && *v >= 0.0 && *v < I64_LIMIT * 2.0 {
+            Box::new(*v as u64)
+        } else {
+            return None;
+        }
+    } else {
+        return None;
+    };
+    match converted.downcast::<T>() {
+        Ok(box v) => Some(v),
+        Err(_) => None
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum PonTranslaterErr {
     BadDependency { property: NamedPropRef, error: Box<BusError> },
+    InvalidPropPath { property: NamedPropRef, segment: PropPathSegment },
+    UnboundArgument { name: String },
     BusError(Box<BusError>),
     CallError { in_pon: Pon, error: Box<PonTranslaterErr> },
     NoSuchFunction { function_name: String },
@@ -197,12 +566,45 @@ pub enum PonTranslaterErr {
     Generic(String)
 }
 
+/// `native_value { <key>: '<value>' }`, where key is `type_name` for values that can't be written
+/// as Pon and `error` for values that failed to evaluate.
+pub fn native_value_placeholder(key: &str, value: &str) -> Pon {
+    Pon::call("native_value", Pon::Object(hashmap!(key => Pon::String(value.to_string()))))
+}
+
+impl PonTranslaterErr {
+    /// This error followed by the errors that caused it, such as the error of a dependency or of
+    /// an argument of a call.
+    pub fn chain(&self) -> Vec<&PonTranslaterErr> {
+        let mut chain = vec![self];
+        let cause = match self {
+            &PonTranslaterErr::CallError { ref error, .. } => Some(&**error),
+            &PonTranslaterErr::BadDependency { ref error, .. } |
+            &PonTranslaterErr::BusError(ref error) => match &**error {
+                &BusError::PonTranslateError { ref err } => Some(err),
+                _ => None
+            },
+            _ => None
+        };
+        if let Some(cause) = cause {
+            chain.extend(cause.chain());
+        }
+        chain
+    }
+}
+
 impl ToString for PonTranslaterErr {
     fn to_string(&self) -> String {
         match self {
             &PonTranslaterErr::BadDependency { ref property, ref error } => {
                 format!("Bad dependency reference \"{}\", got the following error looking it up: {}", property.to_string(), error.to_string())
             }
+            &PonTranslaterErr::InvalidPropPath { ref property, ref segment } => {
+                format!("Can't get {} in \"{}\"", segment.to_string(), property.to_string())
+            }
+            &PonTranslaterErr::UnboundArgument { ref name } => {
+                format!("${} can only be used in the body of a function", name)
+            }
             &PonTranslaterErr::BusError(ref err) => {
                 format!("Buss error \"{}\"", err.to_string())
             }
@@ -0,0 +1,187 @@
+use pon::*;
+use selector::*;
+use entity_match::*;
+
+// Walks over a Pon tree, including the selectors of property references and the values in their
+// entity matches. Each `visit_` method defaults to walking into its children with the matching
+// `walk_` function; implementors override the ones they're interested in and call the `walk_`
+// function themselves if they want to keep descending.
+
+pub trait PonVisitor {
+    fn visit_pon(&mut self, pon: &Pon) {
+        walk_pon(self, pon)
+    }
+    fn visit_call(&mut self, call: &PonCall) {
+        walk_call(self, call)
+    }
+    fn visit_dep_prop_ref(&mut self, named_prop_ref: &NamedPropRef, _resolved: &Option<PropRef>) {
+        walk_named_prop_ref(self, named_prop_ref)
+    }
+    fn visit_prop_ref(&mut self, named_prop_ref: &NamedPropRef) {
+        walk_named_prop_ref(self, named_prop_ref)
+    }
+    fn visit_selector(&mut self, selector: &Selector) {
+        walk_selector(self, selector)
+    }
+    fn visit_entity_match(&mut self, entity_match: &EntityMatch) {
+        walk_entity_match(self, entity_match)
+    }
+}
+
+pub fn walk_pon<V: PonVisitor + ?Sized>(visitor: &mut V, pon: &Pon) {
+    match pon {
+        &Pon::Call(box ref call) => visitor.visit_call(call),
+        &Pon::DepPropRef(ref named_prop_ref, ref resolved) => visitor.visit_dep_prop_ref(named_prop_ref, resolved),
+        &Pon::PropRef(ref named_prop_ref) => visitor.visit_prop_ref(named_prop_ref),
+        &Pon::Selector(ref selector) => visitor.visit_selector(selector),
+        &Pon::Array(ref array) => {
+            for v in array {
+                visitor.visit_pon(v);
+            }
+        },
+        &Pon::Object(ref hm) => {
+            for (_, v) in hm {
+                visitor.visit_pon(v);
+            }
+        },
+        _ => {}
+    }
+}
+
+pub fn walk_call<V: PonVisitor + ?Sized>(visitor: &mut V, call: &PonCall) {
+    visitor.visit_pon(&call.arg)
+}
+
+pub fn walk_named_prop_ref<V: PonVisitor + ?Sized>(visitor: &mut V, named_prop_ref: &NamedPropRef) {
+    visitor.visit_selector(&named_prop_ref.selector)
+}
+
+pub fn walk_selector<V: PonVisitor + ?Sized>(visitor: &mut V, selector: &Selector) {
+    for path in &selector.path {
+        match path {
+            &SelectorPath::Children(ref entity_match) |
+            &SelectorPath::Search(ref entity_match) |
+            &SelectorPath::SearchInverse(ref entity_match) => visitor.visit_entity_match(entity_match),
+            _ => {}
+        }
+    }
+}
+
+pub fn walk_entity_match<V: PonVisitor + ?Sized>(visitor: &mut V, entity_match: &EntityMatch) {
+    match entity_match {
+        &EntityMatch::PropertyValueEquals { ref value, .. } |
+        &EntityMatch::PropertyValueNotEquals { ref value, .. } => visitor.visit_pon(value),
+        &EntityMatch::And(ref a, ref b) |
+        &EntityMatch::Or(ref a, ref b) => {
+            visitor.visit_entity_match(a);
+            visitor.visit_entity_match(b);
+        },
+        _ => {}
+    }
+}
+
+/// Like `PonVisitor`, but rewrites the tree in place. Replace a whole node by assigning to it in
+/// `fold_pon`. The first error aborts the fold.
+pub trait PonFolder {
+    type Error;
+    fn fold_pon(&mut self, pon: &mut Pon) -> Result<(), Self::Error> {
+        walk_pon_mut(self, pon)
+    }
+    fn fold_call(&mut self, call: &mut PonCall) -> Result<(), Self::Error> {
+        walk_call_mut(self, call)
+    }
+    fn fold_dep_prop_ref(&mut self, named_prop_ref: &mut NamedPropRef, _resolved: &mut Option<PropRef>) -> Result<(), Self::Error> {
+        walk_named_prop_ref_mut(self, named_prop_ref)
+    }
+    fn fold_prop_ref(&mut self, named_prop_ref: &mut NamedPropRef) -> Result<(), Self::Error> {
+        walk_named_prop_ref_mut(self, named_prop_ref)
+    }
+    fn fold_selector(&mut self, selector: &mut Selector) -> Result<(), Self::Error> {
+        walk_selector_mut(self, selector)
+    }
+    fn fold_entity_match(&mut self, entity_match: &mut EntityMatch) -> Result<(), Self::Error> {
+        walk_entity_match_mut(self, entity_match)
+    }
+}
+
+pub fn walk_pon_mut<F: PonFolder + ?Sized>(folder: &mut F, pon: &mut Pon) -> Result<(), F::Error> {
+    match pon {
+        &mut Pon::Call(box ref mut call) => folder.fold_call(call),
+        &mut Pon::DepPropRef(ref mut named_prop_ref, ref mut resolved) => folder.fold_dep_prop_ref(named_prop_ref, resolved),
+        &mut Pon::PropRef(ref mut named_prop_ref) => folder.fold_prop_ref(named_prop_ref),
+        &mut Pon::Selector(ref mut selector) => folder.fold_selector(selector),
+        &mut Pon::Array(ref mut array) => {
+            for v in array.iter_mut() {
+                try!(folder.fold_pon(v));
+            }
+            Ok(())
+        },
+        &mut Pon::Object(ref mut hm) => {
+            for (_, v) in hm.iter_mut() {
+                try!(folder.fold_pon(v));
+            }
+            Ok(())
+        },
+        _ => Ok(())
+    }
+}
+
+pub fn walk_call_mut<F: PonFolder + ?Sized>(folder: &mut F, call: &mut PonCall) -> Result<(), F::Error> {
+    folder.fold_pon(&mut call.arg)
+}
+
+pub fn walk_named_prop_ref_mut<F: PonFolder + ?Sized>(folder: &mut F, named_prop_ref: &mut NamedPropRef) -> Result<(), F::Error> {
+    folder.fold_selector(&mut named_prop_ref.selector)
+}
+
+pub fn walk_selector_mut<F: PonFolder + ?Sized>(folder: &mut F, selector: &mut Selector) -> Result<(), F::Error> {
+    for path in selector.path.iter_mut() {
+        match path {
+            &mut SelectorPath::Children(ref mut entity_match) |
+            &mut SelectorPath::Search(ref mut entity_match) |
+            &mut SelectorPath::SearchInverse(ref mut entity_match) => try!(folder.fold_entity_match(entity_match)),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+pub fn walk_entity_match_mut<F: PonFolder + ?Sized>(folder: &mut F, entity_match: &mut EntityMatch) -> Result<(), F::Error> {
+    match entity_match {
+        &mut EntityMatch::PropertyValueEquals { ref mut value, .. } |
+        &mut EntityMatch::PropertyValueNotEquals { ref mut value, .. } => folder.fold_pon(value),
+        &mut EntityMatch::And(ref mut a, ref mut b) |
+        &mut EntityMatch::Or(ref mut a, ref mut b) => {
+            try!(folder.fold_entity_match(a));
+            folder.fold_entity_match(b)
+        },
+        _ => Ok(())
+    }
+}
+
+struct DependencyCollector<'a> {
+    references: &'a mut Vec<PropRef>
+}
+
+impl<'a> PonVisitor for DependencyCollector<'a> {
+    fn visit_dep_prop_ref(&mut self, _named_prop_ref: &NamedPropRef, resolved: &Option<PropRef>) {
+        self.references.push(match resolved {
+            &Some(ref v) => v.clone(),
+            &None => panic!("trying to run build_dependencies_array on non-resolved Pon")
+        });
+    }
+    // Dependencies are only resolved at the top level of an expression, not inside selectors
+    fn visit_selector(&mut self, _selector: &Selector) {}
+}
+
+impl Pon {
+    pub fn visit<V: PonVisitor>(&self, visitor: &mut V) {
+        visitor.visit_pon(self)
+    }
+    pub fn fold<F: PonFolder>(&mut self, folder: &mut F) -> Result<(), F::Error> {
+        folder.fold_pon(self)
+    }
+    pub fn build_dependencies_array(&self, references: &mut Vec<PropRef>) {
+        self.visit(&mut DependencyCollector { references: references });
+    }
+}
@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+use std::sync::{RwLock, Once, ONCE_INIT};
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::fmt;
+
+/// An interned property key. Keys are interned in a global table and never freed, so copying,
+/// comparing and hashing them is as cheap as for an integer. The key keeps its string, so reading
+/// it doesn't go through the table. Keys order by their strings.
+#[derive(Clone, Copy)]
+pub struct PropertyKey {
+    id: u32,
+    key: &'static str
+}
+
+struct PropertyKeyTable {
+    ids: HashMap<&'static str, u32>,
+    keys: Vec<&'static str>
+}
+impl PropertyKeyTable {
+    fn find(&self, key: &str) -> Option<PropertyKey> {
+        self.ids.get(key).map(|id| PropertyKey { id: *id, key: self.keys[*id as usize] })
+    }
+}
+
+static TABLE_INIT: Once = ONCE_INIT;
+static mut TABLE: *const RwLock<PropertyKeyTable> = 0 as *const RwLock<PropertyKeyTable>;
+
+fn table() -> &'static RwLock<PropertyKeyTable> {
+    unsafe {
+        TABLE_INIT.call_once(|| {
+            TABLE = Box::into_raw(Box::new(RwLock::new(PropertyKeyTable {
+                ids: HashMap::new(),
+                keys: Vec::new()
+            })));
+        });
+        &*TABLE
+    }
+}
+
+impl PropertyKey {
+    /// Keys that are already interned are looked up under a read lock, so only the first use of
+    /// a key blocks other threads.
+    pub fn new(key: &str) -> PropertyKey {
+        if let Some(property_key) = table().read().unwrap().find(key) {
+            return property_key;
+        }
+        let mut table = table().write().unwrap();
+        // Another thread may have added it while the lock was released
+        if let Some(property_key) = table.find(key) {
+            return property_key;
+        }
+        let key: &'static str = unsafe { &*Box::into_raw(key.to_string().into_boxed_str()) };
+        let id = table.keys.len() as u32;
+        table.keys.push(key);
+        table.ids.insert(key, id);
+        PropertyKey { id: id, key: key }
+    }
+    pub fn as_str(&self) -> &'static str {
+        self.key
+    }
+}
+
+impl PartialEq for PropertyKey {
+    fn eq(&self, other: &PropertyKey) -> bool {
+        self.id == other.id
+    }
+}
+impl Eq for PropertyKey {}
+
+impl Hash for PropertyKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl Deref for PropertyKey {
+    type Target = str;
+    fn deref(&self) -> &str {
+        self.key
+    }
+}
+
+impl PartialOrd for PropertyKey {
+    fn partial_cmp(&self, other: &PropertyKey) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for PropertyKey {
+    fn cmp(&self, other: &PropertyKey) -> Ordering {
+        if self.id == other.id {
+            Ordering::Equal
+        } else {
+            self.key.cmp(other.key)
+        }
+    }
+}
+
+impl fmt::Debug for PropertyKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.key, f)
+    }
+}
+impl fmt::Display for PropertyKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self.key, f)
+    }
+}
+
+impl<'a> From<&'a str> for PropertyKey {
+    fn from(key: &'a str) -> PropertyKey {
+        PropertyKey::new(key)
+    }
+}
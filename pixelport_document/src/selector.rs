@@ -1,6 +1,7 @@
 
 use document::*;
 use pon::*;
+use pon_diagnostic::*;
 use entity_match::*;
 
 // / == next level
@@ -38,7 +39,7 @@ impl ToString for SelectorRoot {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum SelectorPath {
     Parent,
     Children(EntityMatch),
@@ -115,7 +116,7 @@ impl ToString for SelectorPath {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Selector {
     pub root: SelectorRoot,
     pub path: Vec<SelectorPath>,
@@ -132,7 +133,7 @@ enum PathMatchResult {
 }
 
 impl Selector {
-    pub fn from_string(string: &str) -> Result<Selector, PonParseError> {
+    pub fn from_string(string: &str) -> Result<Selector, PonDiagnostic> {
         selector_from_string(string)
     }
     pub fn this() -> Selector {
@@ -1,6 +1,7 @@
 
 use bus::*;
 use pon::*;
+use property_key::*;
 use std::marker::PhantomData;
 use std::marker::Reflect;
 use pon_translater::*;
@@ -43,14 +44,14 @@ impl Topic {
 #[derive(Debug)]
 pub struct PropertyKeyTopic {
     topic: Topic,
-    keys: Vec<String>
+    keys: Vec<PropertyKey>
 }
 
 impl PropertyKeyTopic {
     pub fn from_slice(keys: &[&str]) -> PropertyKeyTopic {
-        PropertyKeyTopic::new(keys.iter().map(|x| x.to_string()).collect())
+        PropertyKeyTopic::new(keys.iter().map(|x| PropertyKey::new(x)).collect())
     }
-    pub fn new(keys: Vec<String>) -> PropertyKeyTopic {
+    pub fn new(keys: Vec<PropertyKey>) -> PropertyKeyTopic {
         PropertyKeyTopic {
             topic: Topic::new(),
             keys: keys
@@ -0,0 +1,228 @@
+use pon::*;
+use pon_doc::*;
+use document::*;
+
+// Checks property expressions against the `PonDocMatcher`s of the functions they call, without
+// translating anything. Types are compared by name, so only mismatches that are certain to fail
+// at translation time are reported; anything that can't be inferred statically is accepted.
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum ValidationErrorKind {
+    UnknownFunction { function_name: String },
+    MissingField { function_name: String, field: String },
+    WrongEnumValue { function_name: String, expected_one_of: Vec<String>, found: String },
+    WrongType { function_name: String, expected_type: String, found_type: String, found_value: String }
+}
+impl ToString for ValidationErrorKind {
+    fn to_string(&self) -> String {
+        match self {
+            &ValidationErrorKind::UnknownFunction { ref function_name } =>
+                format!("No such function: {}", function_name),
+            &ValidationErrorKind::MissingField { ref function_name, ref field } =>
+                format!("Required field \"{}\" of {} is missing", field, function_name),
+            &ValidationErrorKind::WrongEnumValue { ref function_name, ref expected_one_of, ref found } =>
+                format!("Expected one of {} in {}, found {}", expected_one_of.join(", "), function_name, found),
+            &ValidationErrorKind::WrongType { ref function_name, ref expected_type, ref found_type, ref found_value } =>
+                format!("Expected something of type {} in {}, found \"{}\" of type {}", expected_type, function_name, found_value, found_type)
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct ValidationError {
+    pub prop_ref: PropRef,
+    pub kind: ValidationErrorKind
+}
+impl ToString for ValidationError {
+    fn to_string(&self) -> String {
+        format!("{}.{}: {}", self.prop_ref.entity_id, self.prop_ref.property_key, self.kind.to_string())
+    }
+}
+
+impl Document {
+    /// Checks every property expression against the registered function signatures. Errors are
+    /// sorted by entity id and property key.
+    pub fn validate(&self) -> Vec<ValidationError> {
+        let mut prop_refs: Vec<PropRef> = self.bus.iter().cloned().collect();
+        prop_refs.sort();
+        let mut errors = vec![];
+        for prop_ref in prop_refs {
+            if let Ok(expression) = self.get_property_expression(&prop_ref) {
+                let mut kinds = vec![];
+                Validator { document: self }.validate_pon(expression, &mut kinds);
+                errors.extend(kinds.into_iter().map(|kind| ValidationError { prop_ref: prop_ref.clone(), kind: kind }));
+            }
+        }
+        errors
+    }
+}
+
+struct Validator<'a> {
+    document: &'a Document
+}
+
+impl<'a> Validator<'a> {
+    /// Validates all calls in `pon`, without any expectations on the type of `pon` itself.
+    fn validate_pon(&self, pon: &Pon, errors: &mut Vec<ValidationErrorKind>) {
+        match pon {
+            &Pon::Call(box PonCall { ref function_name, ref arg }) => {
+                match self.document.translater.get_doc_func(function_name) {
+                    Some(doc) => self.validate_matcher(function_name, arg, &doc.arg, errors),
+                    None => {
+                        errors.push(ValidationErrorKind::UnknownFunction { function_name: function_name.clone() });
+                        self.validate_pon(arg, errors);
+                    }
+                }
+            },
+            &Pon::Array(ref array) => for v in array { self.validate_pon(v, errors) },
+            &Pon::Object(ref hm) => {
+                let mut keys: Vec<&String> = hm.keys().collect();
+                keys.sort();
+                for key in keys {
+                    self.validate_pon(&hm[key], errors);
+                }
+            },
+            _ => {}
+        }
+    }
+    fn validate_matcher(&self, function_name: &str, pon: &Pon, matcher: &PonDocMatcher, errors: &mut Vec<ValidationErrorKind>) {
+        match (matcher, pon) {
+            (&PonDocMatcher::Nil, _) => self.validate_pon(pon, errors),
+            (&PonDocMatcher::Capture { ref value, .. }, _) => self.validate_matcher(function_name, pon, value, errors),
+            (&PonDocMatcher::Value { ref typ }, _) => self.validate_type(function_name, pon, typ, errors),
+            (&PonDocMatcher::Array { ref typ }, &Pon::Array(ref array)) => {
+                for v in array {
+                    self.validate_type(function_name, v, typ, errors);
+                }
+            },
+            (&PonDocMatcher::Array { .. }, _) => self.validate_type(function_name, pon, "Vec<Pon>", errors),
+            (&PonDocMatcher::Object { ref typ }, &Pon::Object(ref hm)) => {
+                let mut keys: Vec<&String> = hm.keys().collect();
+                keys.sort();
+                for key in keys {
+                    self.validate_type(function_name, &hm[key], typ, errors);
+                }
+            },
+            (&PonDocMatcher::Map(ref fields), &Pon::Object(ref hm)) => {
+                for field in fields {
+                    match hm.get(&field.var_name) {
+                        Some(v) => self.validate_matcher(function_name, v, &field.value, errors),
+                        None if !field.optional && field.default.is_none() => errors.push(ValidationErrorKind::MissingField {
+                            function_name: function_name.to_string(),
+                            field: field.var_name.clone()
+                        }),
+                        None => {}
+                    }
+                }
+            },
+            (&PonDocMatcher::Object { .. }, _) | (&PonDocMatcher::Map(_), _) =>
+                self.validate_type(function_name, pon, "HashMap<String, Pon>", errors),
+            (&PonDocMatcher::Enum(ref options), &Pon::String(ref value)) => {
+                if !options.iter().any(|option| &option.name == value) {
+                    errors.push(ValidationErrorKind::WrongEnumValue {
+                        function_name: function_name.to_string(),
+                        expected_one_of: options.iter().map(|option| option.name.clone()).collect(),
+                        found: value.clone()
+                    });
+                }
+            },
+            (&PonDocMatcher::Enum(_), _) => self.validate_type(function_name, pon, "String", errors),
+        }
+    }
+    fn validate_type(&self, function_name: &str, pon: &Pon, expected_type: &str, errors: &mut Vec<ValidationErrorKind>) {
+        self.validate_pon(pon, errors);
+        if let Some(found_type) = self.infer_type(pon, &mut vec![]) {
+            if !type_accepts(expected_type, &found_type, pon) && !self.can_coerce(&found_type, expected_type) {
+                errors.push(ValidationErrorKind::WrongType {
+                    function_name: function_name.to_string(),
+                    expected_type: expected_type.to_string(),
+                    found_type: found_type,
+                    found_value: pon.to_string()
+                });
+            }
+        }
+    }
+    fn can_coerce(&self, found_type: &str, expected_type: &str) -> bool {
+        let found_type = normalize_type_name(found_type);
+        let expected_type = normalize_type_name(expected_type);
+        self.document.translater.get_coercion_type_names().into_iter()
+            .any(|(from, to)| short_type_name(from) == found_type && short_type_name(to) == expected_type)
+    }
+    /// The type name `pon` translates to, if it can be known without translating it.
+    fn infer_type(&self, pon: &Pon, visited: &mut Vec<PropRef>) -> Option<String> {
+        match pon {
+            &Pon::Call(box PonCall { ref function_name, .. }) =>
+                self.document.translater.get_doc_func(function_name).map(|doc| doc.target_type_name.clone()),
+            // Fields of structured values aren't typed
+            &Pon::DepPropRef(ref named_prop_ref, _) if named_prop_ref.path.len() > 0 => None,
+            &Pon::DepPropRef(_, Some(ref prop_ref)) => {
+                if visited.contains(prop_ref) {
+                    return None;
+                }
+                visited.push(prop_ref.clone());
+                match self.document.get_property_expression(prop_ref) {
+                    Ok(expression) => self.infer_type(expression, visited),
+                    Err(_) => None
+                }
+            },
+            &Pon::DepPropRef(_, None) => None,
+            &Pon::PropRef(_) => Some("NamedPropRef".to_string()),
+            &Pon::Argument(_) => None,
+            &Pon::Selector(_) => Some("Selector".to_string()),
+            &Pon::Array(_) => Some("Vec<Pon>".to_string()),
+            &Pon::Object(_) => Some("HashMap<String, Pon>".to_string()),
+            &Pon::Number(_) => Some("f32".to_string()),
+            &Pon::Integer(_) => Some("i64".to_string()),
+            &Pon::String(_) => Some("String".to_string()),
+            &Pon::Boolean(_) => Some("bool".to_string()),
+            &Pon::Nil => Some("()".to_string())
+        }
+    }
+}
+
+fn normalize_type_name(typ: &str) -> String {
+    typ.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+// Strips the module paths from a full type name, so that `cgmath::vector::Vector3<f32>` becomes
+// `Vector3<f32>` like the names used in function signatures.
+fn short_type_name(typ: &str) -> String {
+    let mut name = String::new();
+    let mut segment_start = 0;
+    let mut chars = typ.chars().filter(|c| !c.is_whitespace()).peekable();
+    while let Some(c) = chars.next() {
+        if c == ':' && chars.peek() == Some(&':') {
+            chars.next();
+            name.truncate(segment_start);
+        } else {
+            name.push(c);
+            if !(c.is_alphanumeric() || c == '_') {
+                segment_start = name.len();
+            }
+        }
+    }
+    name
+}
+
+fn is_number_type(typ: &str) -> bool {
+    typ == "f32" || typ == "i64" || typ == "u64"
+}
+
+fn type_accepts(expected_type: &str, found_type: &str, pon: &Pon) -> bool {
+    let expected_type = normalize_type_name(expected_type);
+    let found_type = normalize_type_name(found_type);
+    // Functions that return "Pon" evaluate to values of any type, like `if`
+    if expected_type == "Pon" || found_type == "Pon" || expected_type == found_type {
+        return true;
+    }
+    // Integers and numbers are converted into each other at runtime, see `convert_number`
+    if is_number_type(&expected_type) && is_number_type(&found_type) && found_type != "u64" {
+        return match pon {
+            &Pon::Number(v) => v.fract() == 0.0 && v.is_finite() && (expected_type == "i64" || v >= 0.0),
+            &Pon::Integer(v) => expected_type != "u64" || v >= 0,
+            // The value of a call or dependency is only known at runtime
+            _ => true
+        };
+    }
+    false
+}
@@ -9,7 +9,7 @@ fn test_entry_of_wrong_type() {
 
     bus.set_constructor(&PropRef::new(5, "x"), Vec::new(), false, Box::new(|_, _| {
         Ok(Box::new(5))
-    }));
+    })).unwrap();
     let v = bus.get_typed::<String>(&PropRef::new(5, "x"), &PonTranslater::new());
     assert_eq!(v, Err(BusError::EntryOfWrongType { expected: "std::string::String".to_string(), found: "i32".to_string(), value: "5".to_string() }));
 }
@@ -22,7 +22,7 @@ fn test_volatile_dependency_change() {
 
     bus.set_constructor(&PropRef::new(5, "transform"), vec![PropRef::new(5, "rotation_z")], false, Box::new(|_, _| {
         Ok(Box::new(5))
-    }));
+    })).unwrap();
 
     bus.set_value(&PropRef::new(5, "transform"), true, Box::new(5));
 
@@ -31,3 +31,107 @@ fn test_volatile_dependency_change() {
         InvalidatedChange { added: vec![PropRef::new(5, "transform")], removed: vec![] },
     ])
 }
+
+#[test]
+fn test_dependency_cycle_rejected() {
+    let mut bus: Bus = Bus::new();
+
+    bus.set_constructor(&PropRef::new(5, "a"), vec![PropRef::new(5, "b")], false, Box::new(|_, _| Ok(Box::new(5)) )).unwrap();
+    bus.set_constructor(&PropRef::new(5, "b"), vec![PropRef::new(5, "c")], false, Box::new(|_, _| Ok(Box::new(6)) )).unwrap();
+    let res = bus.set_constructor(&PropRef::new(5, "c"), vec![PropRef::new(5, "a")], false, Box::new(|_, _| Ok(Box::new(7)) ));
+    assert_eq!(res, Err(BusError::DependencyCycle { path: vec![PropRef::new(5, "c"), PropRef::new(5, "a"), PropRef::new(5, "b"), PropRef::new(5, "c")] }));
+    assert!(!bus.has(&PropRef::new(5, "c")));
+}
+
+#[test]
+fn test_self_dependency_rejected() {
+    let mut bus: Bus = Bus::new();
+
+    bus.set_value(&PropRef::new(5, "a"), false, Box::new(5));
+    let res = bus.set_constructor(&PropRef::new(5, "a"), vec![PropRef::new(5, "a")], false, Box::new(|_, _| Ok(Box::new(6)) ));
+    assert_eq!(res, Err(BusError::DependencyCycle { path: vec![PropRef::new(5, "a"), PropRef::new(5, "a")] }));
+    assert_eq!(bus.get_typed::<i32>(&PropRef::new(5, "a"), &PonTranslater::new()), Ok(5));
+}
+
+#[test]
+fn test_cache_kept_across_cycles() {
+    let mut doc = Document::from_string(PonTranslater::new(), r#"<Entity name="tmp" x="5.0" y="@this.x" z="7.0" />"#).unwrap();
+    let ent = doc.get_entity_by_name("tmp").unwrap();
+    assert_eq!(doc.get_property::<f32>(ent, "y"), Ok(5.0));
+    doc.close_cycle();
+    doc.close_cycle();
+    assert_eq!(doc.get_property::<f32>(ent, "y"), Ok(5.0));
+    assert_eq!(doc.bus.stats.borrow().n_constructs, 0);
+    assert_eq!(doc.bus.stats.borrow().n_cache_hits, 1);
+}
+
+#[test]
+fn test_close_cycle_skips_unread_volatile() {
+    let mut doc = Document::from_string(PonTranslater::new(), r#"<Entity name="tmp" x="5.0" y="@this.x" />"#).unwrap();
+    let ent = doc.get_entity_by_name("tmp").unwrap();
+    doc.set_property(ent, "v", Pon::Number(1.0), true).unwrap();
+    doc.close_cycle();
+    assert_eq!(doc.bus.stats.borrow().n_constructs, 2);
+    doc.close_cycle();
+    assert_eq!(doc.bus.stats.borrow().n_constructs, 0);
+}
+
+#[test]
+fn test_cache_invalidated_by_dependency() {
+    let mut doc = Document::from_string(PonTranslater::new(), r#"<Entity name="tmp" x="5.0" y="@this.x" z="@this.y" />"#).unwrap();
+    let ent = doc.get_entity_by_name("tmp").unwrap();
+    assert_eq!(doc.get_property::<f32>(ent, "z"), Ok(5.0));
+    doc.close_cycle();
+    doc.set_property(ent, "x", Pon::Number(9.0), false).unwrap();
+    assert_eq!(doc.bus.stats.borrow().n_cache_invalidations, 2);
+    assert_eq!(doc.get_property::<f32>(ent, "z"), Ok(9.0));
+}
+
+#[test]
+fn test_cache_invalidated_once_per_dependent() {
+    let mut doc = Document::from_string(PonTranslater::new(), r#"<Entity name="tmp" x="5.0" y="@this.x" z="@this.x" w="[@this.y, @this.z]" />"#).unwrap();
+    let ent = doc.get_entity_by_name("tmp").unwrap();
+    for key in &["y", "z", "w"] {
+        assert!(doc.get_property_raw(ent, key).is_ok());
+    }
+    doc.close_cycle();
+    doc.set_property(ent, "x", Pon::Number(9.0), false).unwrap();
+    assert_eq!(doc.bus.stats.borrow().n_cache_invalidations, 3);
+}
+
+#[test]
+fn test_volatile_recomputed_each_cycle() {
+    let mut doc = Document::from_string(PonTranslater::new(), r#"<Entity name="tmp" y="@this.x" />"#).unwrap();
+    let ent = doc.get_entity_by_name("tmp").unwrap();
+    doc.set_property(ent, "x", Pon::Number(5.0), true).unwrap();
+    assert_eq!(doc.get_property::<f32>(ent, "y"), Ok(5.0));
+    doc.close_cycle();
+    assert_eq!(doc.bus.stats.borrow().n_cache_invalidations, 2);
+    assert_eq!(doc.get_property::<f32>(ent, "y"), Ok(5.0));
+    assert_eq!(doc.bus.stats.borrow().n_constructs, 2);
+}
+
+#[test]
+fn test_iter_entity() {
+    let mut bus: Bus = Bus::new();
+    bus.set_value(&PropRef::new(1, "x"), false, Box::new(5));
+    bus.set_value(&PropRef::new(1, "y"), false, Box::new(5));
+    bus.set_value(&PropRef::new(2, "x"), false, Box::new(5));
+    bus.remove(&PropRef::new(1, "y"));
+    let props: Vec<PropRef> = bus.iter_entity(1).cloned().collect();
+    assert_eq!(props, vec![PropRef::new(1, "x")]);
+    assert_eq!(bus.iter_entity(3).count(), 0);
+}
+
+#[test]
+fn test_iter_entity_after_rollback() {
+    let mut bus: Bus = Bus::new();
+    bus.set_value(&PropRef::new(1, "x"), false, Box::new(5));
+    bus.begin_transaction();
+    bus.remove(&PropRef::new(1, "x"));
+    bus.set_value(&PropRef::new(2, "x"), false, Box::new(5));
+    bus.rollback_transaction();
+    let props: Vec<PropRef> = bus.iter_entity(1).cloned().collect();
+    assert_eq!(props, vec![PropRef::new(1, "x")]);
+    assert_eq!(bus.iter_entity(2).count(), 0);
+}
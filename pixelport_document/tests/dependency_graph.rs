@@ -0,0 +1,55 @@
+extern crate pixelport_document;
+
+use pixelport_document::*;
+
+const DOC: &'static str = r#"<Root x="5.0">
+    <Entity name="a" y="@root.x" z="@this.y" />
+    <Entity name="b" w="@a.z" v="@this.missing" />
+</Root>"#;
+
+#[test]
+fn test_dependencies() {
+    let doc = Document::from_string(PonTranslater::new(), DOC).unwrap();
+    let a = doc.get_entity_by_name("a").unwrap();
+    let b = doc.get_entity_by_name("b").unwrap();
+    let root = doc.get_root().unwrap();
+    assert_eq!(doc.bus.get_dependencies(&PropRef::new(b, "w"), false), vec![PropRef::new(a, "z")]);
+    assert_eq!(doc.bus.get_dependencies(&PropRef::new(b, "w"), true), vec![PropRef::new(root, "x"), PropRef::new(a, "y"), PropRef::new(a, "z")]);
+    assert_eq!(doc.bus.get_dependencies(&PropRef::new(b, "v"), false), vec![PropRef::new(b, "missing")]);
+}
+
+#[test]
+fn test_dependents() {
+    let doc = Document::from_string(PonTranslater::new(), DOC).unwrap();
+    let a = doc.get_entity_by_name("a").unwrap();
+    let b = doc.get_entity_by_name("b").unwrap();
+    let root = doc.get_root().unwrap();
+    assert_eq!(doc.bus.get_dependents(&PropRef::new(root, "x"), false), vec![PropRef::new(a, "y")]);
+    assert_eq!(doc.bus.get_dependents(&PropRef::new(root, "x"), true), vec![PropRef::new(a, "y"), PropRef::new(a, "z"), PropRef::new(b, "w")]);
+}
+
+#[test]
+fn test_dependency_graph_for_selector() {
+    let doc = Document::from_string(PonTranslater::new(), DOC).unwrap();
+    let a = doc.get_entity_by_name("a").unwrap();
+    let b = doc.get_entity_by_name("b").unwrap();
+    let graph = doc.dependency_graph_for_selector(&Selector::from_string("b").unwrap()).unwrap();
+    let nodes: Vec<PropRef> = graph.nodes.iter().map(|node| node.prop_ref.clone()).collect();
+    assert_eq!(nodes, vec![PropRef::new(a, "z"), PropRef::new(b, "missing"), PropRef::new(b, "v"), PropRef::new(b, "w")]);
+    assert_eq!(graph.edges, vec![
+        DependencyGraphEdge { dependent: PropRef::new(b, "v"), dependency: PropRef::new(b, "missing") },
+        DependencyGraphEdge { dependent: PropRef::new(b, "w"), dependency: PropRef::new(a, "z") }
+    ]);
+    assert!(!graph.nodes[1].exists);
+}
+
+#[test]
+fn test_dependency_graph_to_dot() {
+    let doc = Document::from_string(PonTranslater::new(), r#"<Entity name="a" x="5.0" y="@this.x" />"#).unwrap();
+    assert_eq!(doc.dependency_graph().to_dot(), r#"digraph dependencies {
+    "1.x" [label="1.x\n5.0"];
+    "1.y" [label="1.y\n@this.x"];
+    "1.y" -> "1.x";
+}
+"#);
+}
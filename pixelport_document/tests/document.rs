@@ -18,6 +18,15 @@ fn test_property_get() {
     assert_eq!(doc.get_property::<f32>(ent, "x").unwrap(), 5.0);
 }
 
+#[test]
+fn test_integer_property_get_as_f32() {
+    let mut doc = Document::from_string(PonTranslater::new(), r#"<Entity name="tmp" x="5" y="@this.x" />"#).unwrap();
+    let ent = doc.get_entity_by_name("tmp").unwrap();
+    assert_eq!(doc.get_property::<f32>(ent, "x"), Ok(5.0));
+    assert_eq!(doc.get_property::<f32>(ent, "y"), Ok(5.0));
+    assert_eq!(doc.get_property::<i64>(ent, "x"), Ok(5));
+}
+
 #[test]
 fn test_property_set() {
     let mut doc = Document::from_string(PonTranslater::new(), r#"<Entity name="tmp" x="5.0" />"#).unwrap();
@@ -60,6 +69,7 @@ fn test_property_reference_transfer() {
         doc: "Helps test".to_string(),
         target_type_name: "f32".to_string(),
         arg: PonDocMatcher::Value { typ: "f32".to_string() },
+        pure: true
     });
     let mut doc = Document::from_string(translater, r#"<Entity name="tmp" x="5.0" y="something @this.x" />"#).unwrap();
     let ent = doc.get_entity_by_name("tmp").unwrap();
@@ -131,3 +141,13 @@ fn test_document_to_string_empty() {
     let doc = Document::new(PonTranslater::new());
     assert_eq!(doc.to_string(), "<?xml version=\"1.1\" encoding=\"UTF-8\"?>");
 }
+
+#[test]
+fn test_property_reference_cycle() {
+    let mut doc = Document::from_string(PonTranslater::new(), r#"<Entity name="tmp" x="5.0" y="@this.x" z="@this.y" />"#).unwrap();
+    let ent = doc.get_entity_by_name("tmp").unwrap();
+    assert_eq!(doc.set_property(ent, "x", Pon::from_string("@this.z").unwrap(), false), Err(DocError::BusError(BusError::DependencyCycle {
+        path: vec![PropRef::new(ent, "x"), PropRef::new(ent, "z"), PropRef::new(ent, "y"), PropRef::new(ent, "x")]
+    })));
+    assert_eq!(doc.get_property::<f32>(ent, "z").unwrap(), 5.0);
+}
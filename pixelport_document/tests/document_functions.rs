@@ -0,0 +1,151 @@
+#[macro_use]
+extern crate pixelport_document;
+
+use pixelport_document::*;
+
+fn translater() -> PonTranslater {
+    let mut translater = PonTranslater::new();
+    pon_register_functions!("test", "Test", translater =>
+        "Sums numbers",
+        sum(values: [f32]) f32 => { Ok(values.iter().fold(0.0, |a, b| a + b)) }
+    );
+    translater
+}
+
+const DOC: &'static str = r#"<Root base="0.0">
+    <Function name="offset" body="sum [$value, @root.base]" doc="'Offsets value by root.base'" />
+    <Entity name="tmp" x="offset { value: 5.0 }" />
+</Root>"#;
+
+#[test]
+fn test_call_document_function() {
+    let doc = Document::from_string(translater(), DOC).unwrap();
+    let ent = doc.get_entity_by_name("tmp").unwrap();
+    assert_eq!(doc.get_property::<f32>(ent, "x"), Ok(5.0));
+}
+
+#[test]
+fn test_document_function_body_dependencies() {
+    let mut doc = Document::from_string(translater(), r#"<Root base="1.0">
+        <Function name="offset" body="sum [$value, @root.base]" />
+        <Entity name="tmp" x="offset { value: 5.0 }" />
+    </Root>"#).unwrap();
+    let ent = doc.get_entity_by_name("tmp").unwrap();
+    assert_eq!(doc.get_property::<f32>(ent, "x"), Ok(6.0));
+    let root = doc.get_root().unwrap();
+    doc.set_property(root, "base", Pon::Number(2.0), false).unwrap();
+    doc.close_cycle();
+    assert_eq!(doc.get_property::<f32>(ent, "x"), Ok(7.0));
+}
+
+#[test]
+fn test_call_before_definition() {
+    let doc = Document::from_string(translater(), r#"<Root>
+        <Entity name="tmp" x="double { value: 5.0 }" />
+        <Function name="double" body="sum [$value, $value]" />
+    </Root>"#).unwrap();
+    let ent = doc.get_entity_by_name("tmp").unwrap();
+    assert_eq!(doc.get_property::<f32>(ent, "x"), Ok(10.0));
+}
+
+#[test]
+fn test_arguments_are_lazy() {
+    let doc = Document::from_string(translater(), r#"<Root>
+        <Function name="first" body="$a" />
+        <Entity name="tmp" x="first { a: 1.0, b: no_such_function () }" />
+    </Root>"#).unwrap();
+    let ent = doc.get_entity_by_name("tmp").unwrap();
+    assert_eq!(doc.get_property::<f32>(ent, "x"), Ok(1.0));
+}
+
+#[test]
+fn test_function_body_is_not_evaluated() {
+    let mut doc = Document::from_string(translater(), DOC).unwrap();
+    let function = doc.get_entity_by_name("offset").unwrap();
+    assert_eq!(doc.get_property::<Pon>(function, "body").unwrap().to_string(), "sum [$value, @root.base]");
+    let cycle_changes = doc.close_cycle();
+    assert_eq!(cycle_changes.errors_started, vec![]);
+    assert_eq!(doc.failing_properties(), vec![]);
+}
+
+#[test]
+fn test_missing_argument() {
+    let doc = Document::from_string(translater(), DOC.replace("value: 5.0", "other: 5.0").as_str()).unwrap();
+    let ent = doc.get_entity_by_name("tmp").unwrap();
+    assert!(doc.get_property::<f32>(ent, "x").is_err());
+}
+
+#[test]
+fn test_unbound_argument() {
+    let doc = Document::from_string(translater(), r#"<Entity name="tmp" x="$value" />"#).unwrap();
+    let ent = doc.get_entity_by_name("tmp").unwrap();
+    assert_eq!(doc.get_property::<f32>(ent, "x"), Err(BusError::PonTranslateError {
+        err: PonTranslaterErr::UnboundArgument { name: "value".to_string() }
+    }));
+}
+
+#[test]
+fn test_redefinition_invalidates_callers() {
+    let mut doc = Document::from_string(translater(), DOC).unwrap();
+    let ent = doc.get_entity_by_name("tmp").unwrap();
+    let function = doc.get_entity_by_name("offset").unwrap();
+    let mut topic = AllTopic::new();
+    let cycle_changes = doc.close_cycle();
+    topic.invalidated(&doc.bus, &cycle_changes.invalidations_log);
+    doc.set_property(function, "body", Pon::from_string("sum [$value, 10.0]").unwrap(), false).unwrap();
+    let cycle_changes = doc.close_cycle();
+    let inv = topic.invalidated(&doc.bus, &cycle_changes.invalidations_log);
+    assert!(inv.contains(&PropRef::new(ent, "x")));
+    assert_eq!(doc.get_property::<f32>(ent, "x"), Ok(15.0));
+}
+
+#[test]
+fn test_removing_definition() {
+    let mut doc = Document::from_string(translater(), DOC).unwrap();
+    let ent = doc.get_entity_by_name("tmp").unwrap();
+    let function = doc.get_entity_by_name("offset").unwrap();
+    doc.remove_entity(function).unwrap();
+    assert_eq!(doc.get_property::<f32>(ent, "x"), Err(BusError::PonTranslateError {
+        err: PonTranslaterErr::NoSuchFunction { function_name: "offset".to_string() }
+    }));
+}
+
+#[test]
+fn test_document_function_docs() {
+    let doc = Document::from_string(translater(), DOC).unwrap();
+    let func = doc.translater.get_doc_funcs().into_iter().find(|f| f.name == "offset").unwrap();
+    assert_eq!(func.doc, "Offsets value by root.base");
+    assert_eq!(func.arg, PonDocMatcher::Map(vec![PonDocMapField {
+        var_name: "value".to_string(),
+        optional: false,
+        default: None,
+        value: PonDocMatcher::Value { typ: "Pon".to_string() }
+    }]));
+    assert!(doc.translater.generate_json_docs().contains("\"offset\""));
+}
+
+#[test]
+fn test_recursive_function_is_rejected() {
+    let doc = Document::from_string(translater(), r#"<Root>
+        <Function name="forever" body="sum [1, forever ()]" />
+        <Entity name="tmp" x="forever ()" />
+    </Root>"#).unwrap();
+    let ent = doc.get_entity_by_name("tmp").unwrap();
+    assert!(doc.translater.get_doc_func("forever").is_none());
+    assert!(doc.get_property::<f32>(ent, "x").is_err());
+}
+
+#[test]
+fn test_recursive_body_is_not_set() {
+    let mut doc = Document::from_string(translater(), r#"<Root>
+        <Function name="forever" />
+        <Entity name="tmp" x="forever ()" />
+    </Root>"#).unwrap();
+    let function = doc.get_entity_by_name("forever").unwrap();
+    let ent = doc.get_entity_by_name("tmp").unwrap();
+    assert!(doc.set_property(function, "body", Pon::from_string("sum [1.0, forever ()]").unwrap(), false).is_err());
+    assert!(doc.set_property(function, "body", Pon::from_string("@tmp.x").unwrap(), false).is_err());
+    assert!(!doc.has_property(function, "body"));
+    assert!(doc.translater.get_doc_func("forever").is_none());
+    assert_eq!(doc.bus.get_dependencies(&PropRef::new(ent, "x"), false), vec![]);
+}
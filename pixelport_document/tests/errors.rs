@@ -0,0 +1,78 @@
+extern crate pixelport_document;
+
+use pixelport_document::*;
+
+#[test]
+fn test_failing_properties() {
+    let mut doc = Document::from_string(PonTranslater::new(), r#"<Root x="5.0"><Entity name="a" y="@root.missing" /></Root>"#).unwrap();
+    let a = doc.get_entity_by_name("a").unwrap();
+    assert!(doc.get_property::<f32>(a, "y").is_err());
+    let failing = doc.failing_properties();
+    assert_eq!(failing.len(), 1);
+    assert_eq!(failing[0].0, PropRef::new(a, "y"));
+    match &failing[0].1 {
+        &BusError::PonTranslateError { ref err } => match err.chain().last() {
+            Some(&&PonTranslaterErr::BadDependency { ref error, .. }) =>
+                assert_eq!(**error, BusError::NoSuchEntry { prop_ref: PropRef::new(doc.get_root().unwrap(), "missing") }),
+            cause => panic!("Unexpected cause {:?}", cause)
+        },
+        err => panic!("Unexpected error {:?}", err)
+    }
+}
+
+#[test]
+fn test_errors_started_and_stopped() {
+    let mut doc = Document::from_string(PonTranslater::new(), r#"<Root x="5.0"><Entity name="a" y="@root.x" /></Root>"#).unwrap();
+    let root = doc.get_root().unwrap();
+    let a = doc.get_entity_by_name("a").unwrap();
+    doc.close_cycle();
+    doc.set_property(a, "y", Pon::from_string("@root.missing").unwrap(), false).unwrap();
+    assert!(doc.get_property::<f32>(a, "y").is_err());
+    let changes = doc.close_cycle();
+    assert_eq!(changes.errors_started, vec![PropRef::new(a, "y")]);
+    assert!(changes.errors_stopped.is_empty());
+
+    let changes = doc.close_cycle();
+    assert!(changes.errors_started.is_empty());
+
+    // Fixed without anyone reading the property
+    doc.set_property(root, "missing", Pon::Number(1.0), false).unwrap();
+    let changes = doc.close_cycle();
+    assert_eq!(changes.errors_stopped, vec![PropRef::new(a, "y")]);
+    assert!(doc.failing_properties().is_empty());
+}
+
+#[test]
+fn test_error_removed_with_property() {
+    let mut doc = Document::from_string(PonTranslater::new(), r#"<Root><Entity name="a" y="@root.missing" /></Root>"#).unwrap();
+    let a = doc.get_entity_by_name("a").unwrap();
+    assert!(doc.get_property::<f32>(a, "y").is_err());
+    doc.close_cycle();
+    doc.remove_entity(a).unwrap();
+    let changes = doc.close_cycle();
+    assert_eq!(changes.errors_stopped, vec![PropRef::new(a, "y")]);
+}
+
+#[test]
+fn test_errors_found_without_reading() {
+    let mut doc = Document::from_string(PonTranslater::new(), r#"<Root><Entity name="a" /></Root>"#).unwrap();
+    let a = doc.get_entity_by_name("a").unwrap();
+    doc.close_cycle();
+    doc.set_property(a, "y", Pon::from_string("@root.missing").unwrap(), false).unwrap();
+    let changes = doc.close_cycle();
+    assert_eq!(changes.errors_started, vec![PropRef::new(a, "y")]);
+}
+
+#[test]
+fn test_errors_rolled_back() {
+    let mut doc = Document::from_string(PonTranslater::new(), r#"<Root x="5.0"><Entity name="a" y="@root.x" /></Root>"#).unwrap();
+    let a = doc.get_entity_by_name("a").unwrap();
+    doc.close_cycle();
+    doc.begin_transaction().unwrap();
+    doc.set_property(a, "y", Pon::from_string("@root.missing").unwrap(), false).unwrap();
+    assert!(doc.get_property::<f32>(a, "y").is_err());
+    doc.rollback_transaction().unwrap();
+    assert!(doc.failing_properties().is_empty());
+    let changes = doc.close_cycle();
+    assert!(changes.errors_started.is_empty());
+}
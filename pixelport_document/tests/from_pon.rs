@@ -0,0 +1,137 @@
+#[macro_use]
+extern crate pixelport_document;
+
+use pixelport_document::*;
+use std::collections::HashMap;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Widget {
+    pub size: f32,
+    pub label: Option<String>,
+    pub weight: f32,
+    pub kind: WidgetKind,
+    pub children: Vec<WidgetChild>
+}
+pon_struct!(Widget "widget" {
+    size: (f32),
+    label: (String) optional,
+    weight: (f32) | 2.0,
+    kind: (WidgetKind) | WidgetKind::Small,
+    children: (Vec<WidgetChild>) | Vec::<WidgetChild>::new(),
+});
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct WidgetChild {
+    pub name: String
+}
+pon_struct!(WidgetChild {
+    name: (String),
+});
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum WidgetKind {
+    Small,
+    Large,
+}
+pon_enum!(WidgetKind {
+    Small => "small",
+    Large => "large",
+});
+
+fn from_pon<T: FromPon>(source: &str) -> Result<T, PonTranslaterErr> {
+    T::from_pon(&Pon::from_string(source).unwrap(), &PonTranslater::new(), &Bus::new())
+}
+
+#[test]
+fn test_from_pon_struct() {
+    assert_eq!(from_pon::<Widget>("widget { size: 5, label: 'hi', weight: 1.0, kind: 'large', children: [{ name: 'a' }] }"), Ok(Widget {
+        size: 5.0,
+        label: Some("hi".to_string()),
+        weight: 1.0,
+        kind: WidgetKind::Large,
+        children: vec![WidgetChild { name: "a".to_string() }]
+    }));
+}
+
+#[test]
+fn test_from_pon_struct_defaults() {
+    assert_eq!(from_pon::<Widget>("widget { size: 5.0 }"), Ok(Widget {
+        size: 5.0,
+        label: None,
+        weight: 2.0,
+        kind: WidgetKind::Small,
+        children: vec![]
+    }));
+}
+
+#[test]
+fn test_from_pon_struct_missing_field() {
+    assert_eq!(from_pon::<Widget>("widget { label: 'hi' }"),
+        Err(PonTranslaterErr::RequiredFieldMissing { field: "size".to_string() }));
+}
+
+#[test]
+fn test_from_pon_enum_error() {
+    assert_eq!(from_pon::<WidgetKind>("'medium'"), Err(PonTranslaterErr::EnumValueError {
+        expected_on_of: vec!["\"small\"".to_string(), "\"large\"".to_string()],
+        found: "\"medium\"".to_string()
+    }));
+}
+
+#[test]
+fn test_to_pon_struct() {
+    let widget = Widget { size: 1.0, label: None, weight: 2.0, kind: WidgetKind::Large, children: vec![] };
+    assert_eq!(widget.to_pon(), Pon::from_string("widget { size: 1.0, weight: 2.0, kind: 'large', children: [] }").unwrap());
+}
+
+#[test]
+fn test_struct_round_trip() {
+    let widget = Widget {
+        size: 1.5,
+        label: Some("it's".to_string()),
+        weight: 0.0,
+        kind: WidgetKind::Small,
+        children: vec![WidgetChild { name: "x".to_string() }, WidgetChild { name: "y".to_string() }]
+    };
+    assert_eq!(from_pon::<Widget>(&widget.to_pon().to_string()), Ok(widget));
+}
+
+#[test]
+fn test_request_round_trip() {
+    let request = AppendEntityRequest {
+        entity_id: Some(5),
+        parent: Selector::root(),
+        type_name: "Entity".to_string(),
+        properties: hashmap!{ "x" => Pon::from_string("@this.y + 1").unwrap() }
+    };
+    assert_eq!(from_pon::<AppendEntityRequest>(&request.to_pon().to_string()), Ok(request));
+}
+
+#[test]
+fn test_register_pon_call() {
+    let mut translater = PonTranslater::new();
+    translater.register_pon_call::<Widget>("test", "Test", "A widget");
+    let bus = Bus::new();
+    let pon = Pon::from_string("widget { size: 3 }").unwrap();
+    assert_eq!(translater.translate::<Widget>(&pon, &bus).map(|w| w.size), Ok(3.0));
+    let doc = translater.get_doc_funcs().into_iter().find(|f| f.name == "widget").unwrap();
+    assert_eq!(doc.target_type_name, "Widget");
+    match doc.arg {
+        PonDocMatcher::Map(ref fields) => {
+            let label = fields.iter().find(|f| f.var_name == "label").unwrap();
+            assert!(label.optional);
+            let weight = fields.iter().find(|f| f.var_name == "weight").unwrap();
+            assert_eq!(weight.default, Some("2.0".to_string()));
+            let children = fields.iter().find(|f| f.var_name == "children").unwrap();
+            assert_eq!(children.value, PonDocMatcher::Array { typ: "{\n  name: <String>,\n}".to_string() });
+        },
+        ref v => panic!("Expected a map, got {:?}", v)
+    }
+}
+
+#[test]
+fn test_from_pon_collections() {
+    assert_eq!(from_pon::<Vec<f32>>("[1, 2.5]"), Ok(vec![1.0, 2.5]));
+    let map: HashMap<String, Pon> = from_pon("{ a: @this.x }").unwrap();
+    assert_eq!(map.get("a"), Some(&Pon::from_string("@this.x").unwrap()));
+}
@@ -0,0 +1,179 @@
+extern crate pixelport_document;
+
+use pixelport_document::*;
+
+fn history_doc() -> Document {
+    let mut doc = Document::from_string(PonTranslater::new(), r#"<Root x="5.0"><Entity name="a" y="@root.x"><Entity name="c" z="@a.y" /></Entity><Entity name="b" /></Root>"#).unwrap();
+    doc.enable_history(10);
+    doc
+}
+
+#[test]
+fn test_undo_set_property() {
+    let mut doc = history_doc();
+    let root = doc.get_root().unwrap();
+    let a = doc.get_entity_by_name("a").unwrap();
+    doc.set_property(root, "x", Pon::Number(7.0), false).unwrap();
+    doc.set_property(root, "w", Pon::Number(1.0), false).unwrap();
+    doc.close_cycle();
+    assert_eq!(doc.undo(), Ok(true));
+    assert_eq!(doc.get_property::<f32>(a, "y"), Ok(5.0));
+    assert!(!doc.has_property(root, "w"));
+    assert_eq!(doc.undo(), Ok(false));
+    assert_eq!(doc.redo(), Ok(true));
+    assert_eq!(doc.get_property::<f32>(a, "y"), Ok(7.0));
+    assert_eq!(doc.get_property::<f32>(root, "w"), Ok(1.0));
+    assert_eq!(doc.redo(), Ok(false));
+}
+
+#[test]
+fn test_undo_remove_entity() {
+    let mut doc = history_doc();
+    let root = doc.get_root().unwrap();
+    let a = doc.get_entity_by_name("a").unwrap();
+    let b = doc.get_entity_by_name("b").unwrap();
+    let c = doc.get_entity_by_name("c").unwrap();
+    doc.remove_entity(a).unwrap();
+    doc.close_cycle();
+    assert_eq!(doc.get_children(root).unwrap(), &vec![b]);
+    assert_eq!(doc.undo(), Ok(true));
+    assert_eq!(doc.get_children(root).unwrap(), &vec![a, b]);
+    assert_eq!(doc.get_children(a).unwrap(), &vec![c]);
+    assert_eq!(doc.get_entity_by_name("c"), Some(c));
+    assert_eq!(doc.get_property::<f32>(c, "z"), Ok(5.0));
+    doc.set_property(root, "x", Pon::Number(7.0), false).unwrap();
+    assert_eq!(doc.get_property::<f32>(c, "z"), Ok(7.0));
+}
+
+#[test]
+fn test_undo_append_entity() {
+    let mut doc = history_doc();
+    let root = doc.get_root().unwrap();
+    let a = doc.get_entity_by_name("a").unwrap();
+    let b = doc.get_entity_by_name("b").unwrap();
+    let d = doc.append_entity(None, Some(root), "Entity", Some("d".to_string())).unwrap();
+    doc.set_property(d, "v", Pon::from_string("@root.x").unwrap(), false).unwrap();
+    doc.close_cycle();
+    assert_eq!(doc.undo(), Ok(true));
+    assert_eq!(doc.get_children(root).unwrap(), &vec![a, b]);
+    assert!(!doc.has_property(d, "v"));
+    assert_eq!(doc.redo(), Ok(true));
+    assert_eq!(doc.get_children(root).unwrap(), &vec![a, b, d]);
+    assert_eq!(doc.get_property::<f32>(d, "v"), Ok(5.0));
+}
+
+#[test]
+fn test_undo_clear_children() {
+    let mut doc = history_doc();
+    let root = doc.get_root().unwrap();
+    let a = doc.get_entity_by_name("a").unwrap();
+    let b = doc.get_entity_by_name("b").unwrap();
+    doc.clear_children(root).unwrap();
+    doc.close_cycle();
+    assert_eq!(doc.undo(), Ok(true));
+    assert_eq!(doc.get_children(root).unwrap(), &vec![a, b]);
+}
+
+#[test]
+fn test_steps_are_grouped_per_transaction_and_cycle() {
+    let mut doc = history_doc();
+    let root = doc.get_root().unwrap();
+    doc.set_property(root, "x", Pon::Number(6.0), false).unwrap();
+    doc.close_cycle();
+    doc.transaction(|doc| {
+        try!(doc.set_property(root, "x", Pon::Number(7.0), false));
+        doc.set_property(root, "x", Pon::Number(8.0), false)
+    }).unwrap();
+    doc.begin_transaction().unwrap();
+    doc.set_property(root, "x", Pon::Number(9.0), false).unwrap();
+    doc.rollback_transaction().unwrap();
+    assert_eq!(doc.undo(), Ok(true));
+    assert_eq!(doc.get_property::<f32>(root, "x"), Ok(6.0));
+    assert_eq!(doc.undo(), Ok(true));
+    assert_eq!(doc.get_property::<f32>(root, "x"), Ok(5.0));
+    assert!(!doc.can_undo());
+}
+
+#[test]
+fn test_new_edit_clears_redo() {
+    let mut doc = history_doc();
+    let root = doc.get_root().unwrap();
+    doc.set_property(root, "x", Pon::Number(6.0), false).unwrap();
+    doc.close_cycle();
+    doc.undo().unwrap();
+    assert!(doc.can_redo());
+    doc.set_property(root, "x", Pon::Number(7.0), false).unwrap();
+    assert!(!doc.can_redo());
+}
+
+#[test]
+fn test_history_limit() {
+    let mut doc = history_doc();
+    doc.enable_history(2);
+    let root = doc.get_root().unwrap();
+    for i in 0..4 {
+        doc.set_property(root, "x", Pon::Number(i as f32), false).unwrap();
+        doc.close_cycle();
+    }
+    assert_eq!(doc.undo(), Ok(true));
+    assert_eq!(doc.undo(), Ok(true));
+    assert_eq!(doc.undo(), Ok(false));
+    assert_eq!(doc.get_property::<f32>(root, "x"), Ok(1.0));
+}
+
+#[test]
+fn test_undo_in_transaction() {
+    let mut doc = history_doc();
+    doc.begin_transaction().unwrap();
+    assert_eq!(doc.undo(), Err(DocError::TransactionInProgress));
+}
+
+#[test]
+fn test_undo_redo_requests() {
+    let mut translater = PonTranslater::new();
+    DocumentChannels::pon_document_channels(&mut translater);
+    let mut doc = Document::from_string(translater, r#"<Root x="5.0" />"#).unwrap();
+    doc.enable_history(10);
+    let mut channels = DocumentChannels::new();
+    let root = doc.get_root().unwrap();
+    for i in 0..2 {
+        doc.set_property(root, "x", Pon::Number(i as f32), false).unwrap();
+        doc.close_cycle();
+    }
+    let mut request = |doc: &mut Document, message: &str| {
+        let inc = IncomingMessage::from_string(&doc.translater, &mut doc.bus, ClientId::CAPI, "1".to_string(), message).unwrap();
+        let mut out = vec![];
+        assert!(channels.handle_request(&inc, &mut out, doc));
+        out[0].to_tcpmessage()
+    };
+    assert_eq!(request(&mut doc, "undo { steps: 5 }"), "1 ok 2");
+    assert_eq!(doc.get_property::<f32>(root, "x"), Ok(5.0));
+    assert_eq!(request(&mut doc, "redo {}"), "1 ok 1");
+    assert_eq!(doc.get_property::<f32>(root, "x"), Ok(0.0));
+}
+
+#[test]
+fn test_replacing_native_value_is_not_undone() {
+    let mut doc = history_doc();
+    let root = doc.get_root().unwrap();
+    doc.bus.set_value(&PropRef::new(root, "native"), false, Box::new(3.0f32));
+    doc.set_property(root, "native", Pon::Number(4.0), false).unwrap();
+    doc.close_cycle();
+    assert_eq!(doc.undo(), Ok(false));
+    assert_eq!(doc.get_property::<f32>(root, "native"), Ok(4.0));
+}
+
+#[test]
+fn test_undo_function_body() {
+    let mut doc = Document::from_string(PonTranslater::new(), r#"<Root><Function name="five" /><Entity name="a" x="five ()" /></Root>"#).unwrap();
+    doc.enable_history(10);
+    let function = doc.get_entity_by_name("five").unwrap();
+    let a = doc.get_entity_by_name("a").unwrap();
+    doc.set_property(function, "body", Pon::Number(5.0), false).unwrap();
+    doc.close_cycle();
+    assert_eq!(doc.get_property::<f32>(a, "x"), Ok(5.0));
+    assert_eq!(doc.undo(), Ok(true));
+    assert!(doc.translater.get_doc_func("five").is_none());
+    assert!(doc.get_property::<f32>(a, "x").is_err());
+    assert_eq!(doc.bus.get_dependencies(&PropRef::new(a, "x"), false), vec![]);
+}
@@ -230,3 +230,21 @@ fn test_invalidated_change_twice_deps() {
     let cycle_changes = document.close_cycle(); let inv = topic.invalidated(&document.bus, &cycle_changes.invalidations_log);
     assert_eq_unsorted!(inv, vec![]);
 }
+
+#[test]
+fn test_invalidated_impure_function() {
+    let mut translater = PonTranslater::new();
+    pon_register_functions!("test", "Test", translater =>
+        "Changes every time",
+        #[impure]
+        counter() f32 => { Ok(1.0) }
+    );
+    let mut document = Document::from_string(translater, r#"<Entity name="tmp" x="counter ()" y="@this.x" z="5.0" />"#).unwrap();
+    let mut topic = AllTopic::new();
+    let cycle_changes = document.close_cycle(); let inv = topic.invalidated(&document.bus, &cycle_changes.invalidations_log);
+    assert_eq_unsorted!(inv, vec![PropRef::new(1, "x"), PropRef::new(1, "y"), PropRef::new(1, "z")]);
+    let cycle_changes = document.close_cycle(); let inv = topic.invalidated(&document.bus, &cycle_changes.invalidations_log);
+    assert_eq_unsorted!(inv, vec![PropRef::new(1, "x"), PropRef::new(1, "y")]);
+    let cycle_changes = document.close_cycle(); let inv = topic.invalidated(&document.bus, &cycle_changes.invalidations_log);
+    assert_eq_unsorted!(inv, vec![PropRef::new(1, "x"), PropRef::new(1, "y")]);
+}
@@ -0,0 +1,44 @@
+extern crate pixelport_document;
+
+use std::path::Path;
+use pixelport_document::*;
+
+#[test]
+fn test_missing_file() {
+    match Document::from_file(PonTranslater::new(), Path::new("does/not/exist.pml")) {
+        Err(DocError::Io { ref path, .. }) => assert_eq!(path, "does/not/exist.pml"),
+        Err(err) => panic!("Unexpected error {}", err.to_string()),
+        Ok(_) => panic!("Loaded a file that doesn't exist")
+    }
+}
+
+#[test]
+fn test_xml_error_position() {
+    match Document::from_string(PonTranslater::new(), "<Root>\n  <Entity x=5.0 />\n</Root>") {
+        Err(DocError::Xml { path, line, .. }) => {
+            assert_eq!(path, None);
+            assert_eq!(line, 2);
+        },
+        Err(err) => panic!("Unexpected error {}", err.to_string()),
+        Ok(_) => panic!("Loaded invalid xml")
+    }
+}
+
+#[test]
+fn test_lenient_returns_warnings() {
+    let (doc, warnings) = Document::from_string_with_mode(PonTranslater::new(), r#"<Root x="5.0" y="5.0 +" />"#, LoadMode::Lenient).unwrap();
+    let root = doc.get_root().unwrap();
+    assert_eq!(doc.get_property::<f32>(root, "x"), Ok(5.0));
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].starts_with("Parse error in Root.y"));
+}
+
+#[test]
+fn test_strict_fails_on_warnings() {
+    match Document::from_string_with_mode(PonTranslater::new(), r#"<Root x="5.0" y="5.0 +" />"#, LoadMode::Strict) {
+        Err(DocError::LoadWarnings { warnings, .. }) => assert_eq!(warnings.len(), 1),
+        Err(err) => panic!("Unexpected error {}", err.to_string()),
+        Ok(_) => panic!("Loaded a document with warnings in strict mode")
+    }
+    assert!(Document::from_string_with_mode(PonTranslater::new(), r#"<Root x="5.0" />"#, LoadMode::Strict).is_ok());
+}
@@ -0,0 +1,68 @@
+extern crate pixelport_document;
+extern crate cgmath;
+
+use cgmath::Vector3;
+use pixelport_document::*;
+
+#[derive(Debug, Clone, PartialEq)]
+struct Unregistered(u32);
+
+#[test]
+fn test_value_to_pon() {
+    let translater = PonTranslater::new();
+    let value: Box<BusValue> = Box::new(Vector3::new(1.0f32, 2.0, 3.0));
+    assert_eq!(translater.value_to_pon(&value), Pon::from_string("vec3 { x: 1.0, y: 2.0, z: 3.0 }").unwrap());
+}
+
+#[test]
+fn test_unregistered_value_to_pon() {
+    let translater = PonTranslater::new();
+    let value: Box<BusValue> = Box::new(Unregistered(5));
+    match translater.value_to_pon(&value) {
+        Pon::Call(ref call) => {
+            assert_eq!(call.function_name, "native_value");
+            match &call.arg {
+                &Pon::Object(ref arg) => assert!(arg.contains_key("type_name")),
+                arg => panic!("Unexpected argument {}", arg.to_string())
+            }
+        },
+        pon => panic!("Expected a native_value placeholder, got {}", pon.to_string())
+    }
+}
+
+#[test]
+fn test_native_value_fails_to_translate() {
+    let doc = Document::new(PonTranslater::new());
+    let pon = native_value_placeholder("type_name", "Unregistered");
+    assert!(doc.translater.translate_raw(&pon, &doc.bus).is_err());
+}
+
+#[test]
+fn test_native_values_written_to_xml() {
+    let mut doc = Document::from_string(PonTranslater::new(), r#"<Entity name="a" />"#).unwrap();
+    let a = doc.get_entity_by_name("a").unwrap();
+    doc.bus.set_value(&PropRef::new(a, "position"), false, Box::new(Vector3::new(1.0f32, 2.0, 3.0)));
+    let xml = doc.entity_to_string(a).unwrap();
+    let saved = Document::from_string(PonTranslater::new(), &xml).unwrap();
+    let a = saved.get_entity_by_name("a").unwrap();
+    assert_eq!(saved.get_property_expression(&PropRef::new(a, "position")).unwrap(),
+        &Pon::from_string("vec3 { x: 1.0, y: 2.0, z: 3.0 }").unwrap());
+}
+
+#[test]
+fn test_doc_stream_property_values() {
+    let mut translater = PonTranslater::new();
+    DocumentChannels::pon_document_channels(&mut translater);
+    let mut doc = Document::from_string(translater, r#"<Root list="[1.0, 'two']" object="{ a: 1.0 }" reference="root.list" />"#).unwrap();
+    let mut channels = DocumentChannels::new();
+    let inc = IncomingMessage::from_string(&doc.translater, &mut doc.bus, ClientId::CAPI, "1".to_string(),
+        "doc_stream_create { selector: root, property_regex: '.*' }").unwrap();
+    let mut out = vec![];
+    assert!(channels.handle_request(&inc, &mut out, &mut doc));
+    assert_eq!(out.len(), 1);
+    let message = out[0].to_tcpmessage();
+    assert!(message.contains("property_value: [1.0, 'two']"), "{}", message);
+    assert!(message.contains("property_value: { a: 1.0 }"), "{}", message);
+    assert!(message.contains("property_value: root.list"), "{}", message);
+    assert!(!message.contains("native_value"), "{}", message);
+}
@@ -0,0 +1,132 @@
+#[macro_use]
+extern crate pixelport_document;
+extern crate regex;
+
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::thread;
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+use regex::Regex;
+use pixelport_document::*;
+
+fn values(change: &PropertyChange) -> (Option<f32>, Option<f32>) {
+    let value = |value: &Option<Box<BusValue>>| value.as_ref().and_then(|v| (**v).downcast_ref::<f32>().cloned());
+    (value(&change.old_value), value(&change.new_value))
+}
+
+#[test]
+fn test_observe_property() {
+    let mut doc = Document::from_string(PonTranslater::new(), r#"<Root x="5.0"><Entity name="a" y="@root.x" /></Root>"#).unwrap();
+    let root = doc.get_root().unwrap();
+    let a = doc.get_entity_by_name("a").unwrap();
+    let changes = Rc::new(RefCell::new(vec![]));
+    {
+        let changes = changes.clone();
+        doc.observe(ObserverTarget::Property(PropRef::new(a, "y")), move |change| changes.borrow_mut().push(values(change)));
+    }
+    doc.close_cycle();
+    assert_eq!(*changes.borrow(), vec![]);
+    doc.set_property(root, "x", Pon::Number(6.0), false).unwrap();
+    doc.set_property(root, "x", Pon::Number(7.0), false).unwrap();
+    doc.close_cycle();
+    assert_eq!(*changes.borrow(), vec![(Some(5.0), Some(7.0))]);
+    doc.set_property(root, "z", Pon::Number(7.0), false).unwrap();
+    doc.close_cycle();
+    assert_eq!(changes.borrow().len(), 1);
+}
+
+#[test]
+fn test_observe_key_pattern() {
+    let mut doc = Document::from_string(PonTranslater::new(), r#"<Root><Entity name="a" /><Entity name="b" /></Root>"#).unwrap();
+    let a = doc.get_entity_by_name("a").unwrap();
+    let b = doc.get_entity_by_name("b").unwrap();
+    let (_, receiver) = doc.observe_channel(ObserverTarget::KeyPattern(Regex::new("^volume").unwrap()));
+    doc.set_property(a, "volume", Pon::Number(0.5), false).unwrap();
+    doc.set_property(b, "volume_left", Pon::Number(1.0), false).unwrap();
+    doc.set_property(b, "pan", Pon::Number(1.0), false).unwrap();
+    doc.close_cycle();
+    let mut changes: Vec<(PropRef, (Option<f32>, Option<f32>))> = thread::spawn(move || {
+        let mut changes = vec![];
+        while let Ok(change) = receiver.try_recv() {
+            changes.push((change.prop_ref, values(&change)));
+        }
+        changes
+    }).join().unwrap();
+    changes.sort_by(|x, y| x.0.cmp(&y.0));
+    assert_eq!(changes, vec![
+        (PropRef::new(a, "volume"), (None, Some(0.5))),
+        (PropRef::new(b, "volume_left"), (None, Some(1.0)))
+    ]);
+}
+
+#[test]
+fn test_observe_selector() {
+    let mut doc = Document::from_string(PonTranslater::new(), r#"<Root><Entity name="a" x="1.0" /><Entity name="b" x="1.0" /></Root>"#).unwrap();
+    let a = doc.get_entity_by_name("a").unwrap();
+    let b = doc.get_entity_by_name("b").unwrap();
+    // Read before the observer is added, so the old value is known
+    assert_eq!(doc.get_property::<f32>(a, "x"), Ok(1.0));
+    let (_, receiver) = doc.observe_channel(ObserverTarget::Selector { selector: Selector::from_string("a").unwrap(), key_pattern: None });
+    doc.set_property(a, "x", Pon::Number(2.0), false).unwrap();
+    doc.set_property(b, "x", Pon::Number(2.0), false).unwrap();
+    doc.close_cycle();
+    let change = receiver.try_recv().unwrap();
+    assert_eq!(change.prop_ref, PropRef::new(a, "x"));
+    assert_eq!(values(&change), (Some(1.0), Some(2.0)));
+    assert!(receiver.try_recv().is_err());
+}
+
+#[test]
+fn test_observing_selector_evaluates_nothing() {
+    let mut doc = Document::from_string(PonTranslater::new(), r#"<Root x="1.0"><Entity name="a" y="@root.x" /></Root>"#).unwrap();
+    let a = doc.get_entity_by_name("a").unwrap();
+    let constructs = doc.bus.stats.borrow().n_constructs;
+    let (_, receiver) = doc.observe_channel(ObserverTarget::Selector { selector: Selector::from_string("a").unwrap(), key_pattern: None });
+    assert_eq!(doc.bus.stats.borrow().n_constructs, constructs);
+    doc.set_property(a, "y", Pon::Number(2.0), false).unwrap();
+    doc.close_cycle();
+    let change = receiver.try_recv().unwrap();
+    assert_eq!(values(&change), (None, Some(2.0)));
+}
+
+#[test]
+fn test_observer_removed_with_entity() {
+    let mut doc = Document::from_string(PonTranslater::new(), r#"<Root><Entity name="a" x="1.0" /></Root>"#).unwrap();
+    let a = doc.get_entity_by_name("a").unwrap();
+    let (id, receiver) = doc.observe_channel(ObserverTarget::Property(PropRef::new(a, "x")));
+    doc.remove_entity(a).unwrap();
+    doc.close_cycle();
+    assert_eq!(values(&receiver.try_recv().unwrap()), (Some(1.0), None));
+    assert!(receiver.try_recv().is_err());
+    assert!(!doc.unobserve(id));
+}
+
+#[test]
+fn test_observer_removed_with_receiver() {
+    let mut doc = Document::from_string(PonTranslater::new(), r#"<Root x="1.0" />"#).unwrap();
+    let root = doc.get_root().unwrap();
+    let (id, receiver) = doc.observe_channel(ObserverTarget::Property(PropRef::new(root, "x")));
+    drop(receiver);
+    doc.set_property(root, "x", Pon::Number(2.0), false).unwrap();
+    doc.close_cycle();
+    assert!(!doc.unobserve(id));
+}
+
+static CALLS: AtomicUsize = ATOMIC_USIZE_INIT;
+
+#[test]
+fn test_observed_volatile_property_evaluated_again() {
+    let mut translater = PonTranslater::new();
+    pon_register_functions!("test", "Test", translater =>
+        "Counts its calls",
+        #[impure]
+        count_calls() f32 => { Ok(CALLS.fetch_add(1, Ordering::SeqCst) as f32) }
+    );
+    let mut doc = Document::from_string(translater, r#"<Entity name="tmp" x="count_calls ()" y="@this.x" />"#).unwrap();
+    let ent = doc.get_entity_by_name("tmp").unwrap();
+    let (_, _receiver) = doc.observe_channel(ObserverTarget::Property(PropRef::new(ent, "y")));
+    doc.close_cycle();
+    let calls = CALLS.load(Ordering::SeqCst);
+    assert_eq!(doc.get_property::<f32>(ent, "y"), Ok(calls as f32));
+    assert_eq!(CALLS.load(Ordering::SeqCst), calls + 1);
+}
@@ -26,7 +26,39 @@ fn test_float_empty_space() {
 #[test]
 fn test_integer() {
     let v = Pon::from_string("5");
-    assert_eq!(v, Ok(Pon::Number(5.0)));
+    assert_eq!(v, Ok(Pon::Integer(5)));
+}
+
+#[test]
+fn test_neg_integer() {
+    let v = Pon::from_string("-5");
+    assert_eq!(v, Ok(Pon::Integer(-5)));
+}
+
+#[test]
+fn test_large_integer() {
+    let v = Pon::from_string("9007199254740993");
+    assert_eq!(v, Ok(Pon::Integer(9007199254740993)));
+}
+
+#[test]
+fn test_integer_overflow() {
+    assert!(Pon::from_string("9223372036854775807").is_ok());
+    assert!(Pon::from_string("9223372036854775808").is_err());
+    assert!(Pon::from_string("-9223372036854775809").is_err());
+}
+
+#[test]
+fn test_large_u64_to_pon() {
+    let v = (::std::i64::MAX as u64) + 1;
+    assert_eq!(v.to_pon(), Pon::from_string("native_value { error: '9223372036854775808 doesn\'t fit in a Pon integer' }").unwrap());
+}
+
+#[test]
+fn test_entity_id_round_trip() {
+    let id: u64 = (1 << 24) + 1;
+    let v = Pon::from_string(&id.to_pon().to_string());
+    assert_eq!(v, Ok(Pon::Integer(16777217)));
 }
 
 #[test]
@@ -83,7 +115,7 @@ fn test_object_complex() {
     let v = Pon::from_string("{ a: [0.0, 0.5], b: [0] }");
     assert_eq!(v, Ok(Pon::Object(hashmap!{
         "a".to_string() => Pon::Array(vec![Pon::Number(0.0), Pon::Number(0.5)]),
-        "b".to_string() => Pon::Array(vec![Pon::Number(0.0)])
+        "b".to_string() => Pon::Array(vec![Pon::Integer(0)])
     })));
 }
 
@@ -122,7 +154,7 @@ fn test_transform_arg() {
     let v = Pon::from_string("static_mesh { vertices: [0.0, -0.5], indices: [0, 1] }");
     let mut hm = HashMap::new();
     hm.insert("vertices".to_string(), Pon::Array(vec![Pon::Number(0.0), Pon::Number(-0.5)]));
-    hm.insert("indices".to_string(),  Pon::Array(vec![Pon::Number(0.0), Pon::Number(1.0)]));
+    hm.insert("indices".to_string(),  Pon::Array(vec![Pon::Integer(0), Pon::Integer(1)]));
     assert_eq!(v, Ok(Pon::Call(Box::new(PonCall { function_name: "static_mesh".to_string(), arg: Pon::Object(hm) }))));
 }
 
@@ -192,3 +224,77 @@ fn test_comment() {
     ");
     assert_eq!(v, Ok(Pon::Number(5.0)));
 }
+
+fn call(function_name: &str, args: Vec<Pon>) -> Pon {
+    Pon::call(function_name, Pon::Array(args))
+}
+
+fn dep(string: &str) -> Pon {
+    Pon::DepPropRef(NamedPropRef::from_string(string).unwrap(), None)
+}
+
+#[test]
+fn test_infix_precedence() {
+    let v = Pon::from_string("@this.a * 2 + 1");
+    assert_eq!(v, Ok(call("add", vec![call("mul", vec![dep("this.a"), Pon::Integer(2)]), Pon::Integer(1)])));
+}
+
+#[test]
+fn test_infix_flat_chain() {
+    let v = Pon::from_string("1 + 2 + 3");
+    assert_eq!(v, Ok(call("add", vec![Pon::Integer(1), Pon::Integer(2), Pon::Integer(3)])));
+}
+
+#[test]
+fn test_infix_left_associative() {
+    let v = Pon::from_string("1 - 2 - 3");
+    assert_eq!(v, Ok(call("sub", vec![call("sub", vec![Pon::Integer(1), Pon::Integer(2)]), Pon::Integer(3)])));
+}
+
+#[test]
+fn test_infix_mixed_chain() {
+    let v = Pon::from_string("1 + 2 - 3 + 4");
+    assert_eq!(v, Ok(call("add", vec![call("sub", vec![call("add", vec![Pon::Integer(1), Pon::Integer(2)]), Pon::Integer(3)]), Pon::Integer(4)])));
+}
+
+#[test]
+fn test_infix_parentheses() {
+    let v = Pon::from_string("(1 + 2) * 3");
+    assert_eq!(v, Ok(call("mul", vec![call("add", vec![Pon::Integer(1), Pon::Integer(2)]), Pon::Integer(3)])));
+}
+
+#[test]
+fn test_infix_boolean() {
+    let v = Pon::from_string("!@this.a || @this.b >= 5 && @this.c != 2.5");
+    assert_eq!(v, Ok(call("or", vec![
+        Pon::call("not", dep("this.a")),
+        call("and", vec![
+            call("gte", vec![dep("this.b"), Pon::Integer(5)]),
+            call("neq", vec![dep("this.c"), Pon::Number(2.5)])
+        ])
+    ])));
+}
+
+#[test]
+fn test_infix_negation() {
+    assert_eq!(Pon::from_string("-5"), Ok(Pon::Integer(-5)));
+    assert_eq!(Pon::from_string("-@this.x"), Ok(Pon::call("neg", dep("this.x"))));
+    assert_eq!(Pon::from_string("5 - -3"), Ok(call("sub", vec![Pon::Integer(5), Pon::Integer(-3)])));
+}
+
+#[test]
+fn test_infix_call_argument() {
+    let v = Pon::from_string("abs @this.x * 2");
+    assert_eq!(v, Ok(call("mul", vec![Pon::call("abs", dep("this.x")), Pon::Integer(2)])));
+    let v = Pon::from_string("abs (@this.x * 2)");
+    assert_eq!(v, Ok(Pon::call("abs", call("mul", vec![dep("this.x"), Pon::Integer(2)]))));
+}
+
+#[test]
+fn test_infix_in_object() {
+    let v = Pon::from_string("vec3 { x: @this.a / 2, y: 10 % 3 }");
+    assert_eq!(v, Ok(Pon::call("vec3", Pon::Object(hashmap!{
+        "x" => call("div", vec![dep("this.a"), Pon::Integer(2)]),
+        "y" => call("modulo", vec![Pon::Integer(10), Pon::Integer(3)])
+    }))));
+}
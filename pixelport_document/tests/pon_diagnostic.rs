@@ -0,0 +1,74 @@
+extern crate pixelport_document;
+
+use pixelport_document::*;
+
+#[test]
+fn test_diagnostic_position() {
+    let err = Pon::from_string("{ x: 5, y: ? }").err().unwrap();
+    assert_eq!(err.line, 1);
+    assert_eq!(err.column, 12);
+    assert_eq!(err.span_start, 11);
+    assert_eq!(err.span_end, 12);
+    assert_eq!(err.found(), "?");
+    assert!(!err.expected.is_empty());
+}
+
+#[test]
+fn test_diagnostic_multiline() {
+    let err = Pon::from_string("{\n  x: 5,\n  y: ?\n}").err().unwrap();
+    assert_eq!(err.line, 3);
+    assert_eq!(err.column, 6);
+    assert_eq!(err.found(), "?");
+}
+
+#[test]
+fn test_diagnostic_end_of_input() {
+    let err = Pon::from_string("[1, 2").err().unwrap();
+    assert_eq!(err.span_start, 5);
+    assert_eq!(err.span_end, 5);
+    assert_eq!(err.found(), "");
+    assert!(err.message().starts_with("Unexpected end of input"));
+}
+
+#[test]
+fn test_diagnostic_multibyte_span() {
+    let err = Pon::from_string("[1, ☃]").err().unwrap();
+    assert_eq!(err.column, 5);
+    assert_eq!(err.found(), "☃");
+    assert_eq!(err.span_end - err.span_start, '☃'.len_utf8());
+}
+
+#[test]
+fn test_diagnostic_render() {
+    let err = Pon::from_string("{\n  x: 5,\n  y: ?\n}").err().unwrap();
+    let rendered = err.render();
+    let lines: Vec<&str> = rendered.lines().collect();
+    assert!(lines[0].starts_with("error: Unexpected \"?\""));
+    assert_eq!(lines[1], "  --> line 3, column 6");
+    assert_eq!(lines[3], "3 |   y: ?");
+    assert_eq!(lines[4], "  |      ^");
+}
+
+#[test]
+fn test_selector_diagnostic() {
+    let err = Selector::from_string("root:[x=5").err().unwrap();
+    assert_eq!(err.source, "root:[x=5");
+    assert_eq!(err.found(), "");
+}
+
+#[test]
+fn test_propref_diagnostic() {
+    let err = NamedPropRef::from_string("@this.").err().unwrap();
+    assert_eq!(err.column, 7);
+}
+
+#[test]
+fn test_incoming_message_parse_error() {
+    let translater = PonTranslater::new();
+    let mut bus = Bus::new();
+    let err = IncomingMessage::from_string(&translater, &mut bus, ClientId::CAPI, "1".to_string(), "set_properties {").err().unwrap();
+    match err.message {
+        Err(RequestError { error_type: RequestErrorType::BadRequest, message }) => assert!(message.contains("^")),
+        _ => panic!("Expected a bad request")
+    }
+}
@@ -0,0 +1,59 @@
+extern crate pixelport_document;
+
+use pixelport_document::*;
+
+use std::collections::HashSet;
+use std::f32;
+
+fn distinct(strings: &[&str]) -> usize {
+    let set: HashSet<Pon> = strings.iter().map(|s| Pon::from_string(s).unwrap()).collect();
+    set.len()
+}
+
+#[test]
+fn test_equal_pons_are_one_key() {
+    assert_eq!(distinct(&["vec3 { x: 1.0, y: 2.0 }", "vec3 { x: 1.0, y: 2.0 }"]), 1);
+}
+
+#[test]
+fn test_object_key_order_does_not_matter() {
+    assert_eq!(distinct(&["{ a: 1, b: 2, c: 3 }", "{ c: 3, b: 2, a: 1 }", "{ b: 2, a: 1, c: 3 }"]), 1);
+}
+
+#[test]
+fn test_different_pons_are_different_keys() {
+    assert_eq!(distinct(&["1", "1.0", "'1'", "[1]", "{ a: 1 }", "this.a", "this", "add [1, 2]", "nil"]), 9);
+}
+
+#[test]
+fn test_nan_equals_itself() {
+    assert_eq!(Pon::Number(f32::NAN), Pon::Number(f32::NAN));
+    assert_eq!(Pon::Number(f32::NAN), Pon::Number(-f32::NAN));
+    assert_eq!(distinct(&["NaN", "NaN"]), 1);
+}
+
+#[test]
+fn test_negative_zero_equals_zero() {
+    assert_eq!(Pon::Number(-0.0), Pon::Number(0.0));
+    let mut set = HashSet::new();
+    set.insert(Pon::Number(-0.0));
+    set.insert(Pon::Number(0.0));
+    assert_eq!(set.len(), 1);
+}
+
+#[test]
+fn test_canonical_f32_bits() {
+    assert_eq!(canonical_f32_bits(0.0), canonical_f32_bits(-0.0));
+    assert_eq!(canonical_f32_bits(f32::NAN), canonical_f32_bits(-f32::NAN));
+    assert!(canonical_f32_bits(1.0) != canonical_f32_bits(-1.0));
+}
+
+#[test]
+fn test_selector_as_key() {
+    let a = Selector::from_string("root:[name=x]/[mesh]").unwrap();
+    let b = Selector::from_string("root:[name=x]/[mesh]").unwrap();
+    let mut set = HashSet::new();
+    set.insert(a);
+    set.insert(b);
+    assert_eq!(set.len(), 1);
+}
@@ -0,0 +1,130 @@
+#[macro_use]
+extern crate pixelport_document;
+extern crate serde_json;
+
+use pixelport_document::*;
+use serde_json::value::Value;
+
+fn assert_round_trip(source: &str) {
+    let pon = Pon::from_string(source).unwrap();
+    let json = pon.to_json_string();
+    assert_eq!(Pon::from_json_string(&json), Ok(pon), "encoded as {}", json);
+}
+
+fn json(string: &str) -> Value {
+    serde_json::from_str(string).unwrap()
+}
+
+#[test]
+fn test_json_round_trip_corpus() {
+    let corpus = vec![
+        "()",
+        "true",
+        "5",
+        "-9223372036854775808",
+        "5.5",
+        "0.1",
+        "-0.0",
+        "inf",
+        "-inf",
+        "'hello \\'world\\''",
+        "[1, 2.5, 'three', [()]]",
+        "{ a: 1, 'b c': { d: [true] } }",
+        "{ '$call': 1 }",
+        "{ '$object': { '$x': 2 } }",
+        "vec3 { x: 1.0, y: 2.0 }",
+        "static_mesh ()",
+        "@this.x * 2 + 1",
+        "@root:[name='some thing'].x",
+        "this:[x=5].y",
+        "root/Entity:![x=5]|parent|",
+        "#567",
+    ];
+    for source in corpus {
+        assert_round_trip(source);
+    }
+}
+
+#[test]
+fn test_json_plain_values() {
+    assert_eq!(Pon::from_string("{ a: [1, 2.5, 'x', true, ()] }").unwrap().to_json(),
+        json(r#"{ "a": [1, 2.5, "x", true, null] }"#));
+    assert_eq!(Pon::from_json(&json(r#"{ "a": [1, 2.5, "x", true, null] }"#)),
+        Pon::from_string("{ a: [1, 2.5, 'x', true, ()] }"));
+}
+
+#[test]
+fn test_json_call() {
+    assert_eq!(Pon::from_string("vec3 { x: 1 }").unwrap().to_json(), json(r#"{ "$call": "vec3", "arg": { "x": 1 } }"#));
+    assert_eq!(Pon::from_string("identity ()").unwrap().to_json(), json(r#"{ "$call": "identity" }"#));
+}
+
+#[test]
+fn test_json_references() {
+    assert_eq!(Pon::from_string("root:[name=a]").unwrap().to_json(), json(r#"{ "$selector": "root:[name=a]" }"#));
+    assert_eq!(Pon::from_string("this.x").unwrap().to_json(), json(r#"{ "$propref": "this.x" }"#));
+    assert_eq!(Pon::from_string("@this.x").unwrap().to_json(), json(r#"{ "$dep": "this.x" }"#));
+}
+
+#[test]
+fn test_json_resolved_dep() {
+    let pon = Pon::DepPropRef(NamedPropRef::from_string("this.x").unwrap(), Some(PropRef::new(5, "x")));
+    let value = json(r#"{ "$dep": "this.x", "resolved": { "entity_id": 5, "property_key": "x" } }"#);
+    assert_eq!(pon.to_json(), value);
+    assert_eq!(Pon::from_json(&value), Ok(pon));
+}
+
+#[test]
+fn test_json_nan() {
+    let value = Pon::Number(std::f32::NAN).to_json();
+    assert_eq!(value, json(r#"{ "$number": "NaN" }"#));
+    match Pon::from_json(&value) {
+        Ok(Pon::Number(v)) => assert!(v.is_nan()),
+        v => panic!("Expected NaN, got {:?}", v)
+    }
+}
+
+#[test]
+fn test_json_escaped_object() {
+    let pon = Pon::Object(hashmap!{ "$call" => Pon::String("not a call".to_string()) });
+    assert_eq!(pon.to_json(), json(r#"{ "$object": { "$call": "not a call" } }"#));
+}
+
+#[test]
+fn test_json_errors() {
+    assert_eq!(Pon::from_json(&json(r#"{ "$what": 1 }"#)), Err(PonJsonError::UnknownTag("$what".to_string())));
+    assert_eq!(Pon::from_json(&json(r#"{ "$call": "a", "$selector": "b" }"#)),
+        Err(PonJsonError::MultipleTags(vec!["$call".to_string(), "$selector".to_string()])));
+    assert_eq!(Pon::from_json(&json(r#"{ "$call": 5 }"#)),
+        Err(PonJsonError::InvalidField { tag: "$call".to_string(), field: "$call".to_string(), expected: "a string".to_string() }));
+    match Pon::from_json(&json(r#"{ "$propref": "this." }"#)) {
+        Err(PonJsonError::InvalidSyntax { ref tag, .. }) => assert_eq!(tag, "$propref"),
+        v => panic!("Expected a syntax error, got {:?}", v)
+    }
+    match Pon::from_json_string("{ nope") {
+        Err(PonJsonError::InvalidJson(_)) => {},
+        v => panic!("Expected a json error, got {:?}", v)
+    }
+}
+
+#[test]
+fn test_incoming_json_message() {
+    let translater = PonTranslater::new();
+    let mut bus = Bus::new();
+    let inc = IncomingMessage::from_tcpstring(&translater, &mut bus, ClientId::CAPI,
+        r#"5 { "$call": "close_stream", "arg": { "channel_id": "3" } }"#);
+    // close_stream isn't registered in a bare translater, but the message should still be decoded as json
+    let err = inc.err().unwrap();
+    assert_eq!(err.encoding, MessageEncoding::Json);
+    assert_eq!(err.channel_id, "5");
+    assert!(err.to_tcpmessage().starts_with(r#"5 err {"$call":"request_error""#));
+}
+
+#[test]
+fn test_incoming_pon_message() {
+    let translater = PonTranslater::new();
+    let mut bus = Bus::new();
+    let err = IncomingMessage::from_tcpstring(&translater, &mut bus, ClientId::CAPI, "5 close_stream { channel_id: '3' }").err().unwrap();
+    assert_eq!(err.encoding, MessageEncoding::Pon);
+    assert!(err.to_tcpmessage().starts_with("5 err request_error"));
+}
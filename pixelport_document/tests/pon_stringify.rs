@@ -0,0 +1,153 @@
+#[macro_use]
+extern crate pixelport_document;
+
+use pixelport_document::*;
+
+fn assert_round_trip(pon: Pon) {
+    let string = pon.to_string();
+    assert_eq!(Pon::from_string(&string), Ok(pon), "stringified as {}", string);
+}
+
+fn assert_source_round_trip(source: &str) {
+    assert_round_trip(Pon::from_string(source).unwrap());
+}
+
+#[test]
+fn test_round_trip_corpus() {
+    let corpus = vec![
+        "()",
+        "true",
+        "false",
+        "5",
+        "-5",
+        "9223372036854775807",
+        "5.0",
+        "-0.5",
+        "0.1",
+        "3.4028235e38",
+        "'hello'",
+        "''",
+        "'it\\'s'",
+        "'back\\\\slash'",
+        "'line\\nbreak\\ttab\\rreturn'",
+        "'not a comment: //'",
+        "'åäö ☃ 日本'",
+        "[]",
+        "[1, 2.5, 'three', [4], { five: 5 }]",
+        "{}",
+        "{ a: 1, b: { c: [true, false] } }",
+        "{ 'not an identifier': 1, 'with\\'quote': 2 }",
+        "vec3 { x: 1.0, y: 2.0, z: 3.0 }",
+        "translate vec3 { x: 1.0 }",
+        "static_mesh ()",
+        "add [@this.x, @parent.y, @root.z]",
+        "@some.test",
+        "some:[name=else].test",
+        "root:[name='with space']",
+        "root:[name='it\\'s'].x",
+        "@root/Entity:![x=5]|parent|.y",
+        "#567",
+        "@#567.x",
+        "@root.camera_pos.x",
+        "@this.points[2].y",
+        "this.config.'not an identifier'[0]",
+        "root:[[x=5] && [y='a]b']]",
+        "root:[[x=5] || [y!=3.5]]",
+        "this:[visible]/*",
+        "trueish",
+        "true_color 5",
+        "info.x",
+        "@this.a * 2 + 1",
+        "(1 + 2) + 3",
+        "1 + (2 + 3)",
+        "1 - (2 - 3)",
+        "(1 < 2) == true",
+        "-(@this.x + 1)",
+        "- 5",
+        "- -5",
+        "!!@this.visible",
+        "abs (@this.x * 2)",
+        "(this) / 2",
+        "root:[x=(1 + 2)]",
+        "add [1]",
+        "sub [1, 2, 3]",
+        "not [true, false]",
+    ];
+    for source in corpus {
+        assert_source_round_trip(source);
+    }
+}
+
+#[test]
+fn test_round_trip_floats() {
+    for v in vec![0.0, -0.0, 1.0, -1.0, 0.1, 1.0e-40, 1.0e38, 16777217.0, ::std::f32::MAX, ::std::f32::MIN_POSITIVE,
+                  ::std::f32::INFINITY, ::std::f32::NEG_INFINITY] {
+        assert_round_trip(Pon::Number(v));
+    }
+}
+
+#[test]
+fn test_round_trip_nan() {
+    match Pon::from_string(&Pon::Number(::std::f32::NAN).to_string()) {
+        Ok(Pon::Number(v)) => assert!(v.is_nan()),
+        v => panic!("Expected NaN, got {:?}", v)
+    }
+}
+
+#[test]
+fn test_round_trip_control_chars() {
+    assert_round_trip(Pon::String("bell \u{7} and null \u{0} and delete \u{7f}".to_string()));
+}
+
+#[test]
+fn test_round_trip_object_keys() {
+    assert_round_trip(Pon::Object(hashmap!{
+        "1st" => Pon::Integer(1),
+        "" => Pon::Nil,
+        "a b" => Pon::Boolean(true)
+    }));
+}
+
+#[test]
+fn test_number_stringifies_with_decimal_point() {
+    assert_eq!(Pon::Number(4.0).to_string(), "4.0");
+    assert_eq!(Pon::Integer(4).to_string(), "4");
+}
+
+#[test]
+fn test_string_escapes() {
+    assert_eq!(Pon::String("it's a \\ \n".to_string()).to_string(), "'it\\'s a \\\\ \\n'");
+    assert_eq!(Pon::from_string("'\\u{2603}'"), Ok(Pon::String("☃".to_string())));
+}
+
+#[test]
+fn test_document_to_string_round_trip() {
+    let doc = Document::from_string(PonTranslater::new(), r#"<Entity name="tmp" x="'quote\' and &quot;double&quot;'" y="[1, 2.0, 'a\nb']" />"#).unwrap();
+    let reloaded = Document::from_string(PonTranslater::new(), &doc.to_string()).unwrap();
+    let ent = doc.get_entity_by_name("tmp").unwrap();
+    let reloaded_ent = reloaded.get_entity_by_name("tmp").unwrap();
+    for key in vec!["x", "y"] {
+        assert_eq!(reloaded.get_property_expression(&PropRef::new(reloaded_ent, key)),
+            doc.get_property_expression(&PropRef::new(ent, key)));
+    }
+}
+
+#[test]
+fn test_infix_stringify() {
+    let cases = vec![
+        ("add [mul [@this.a, 2], 1]", "@this.a * 2 + 1"),
+        ("mul [add [1, 2], 3]", "(1 + 2) * 3"),
+        ("add [1, 2, 3]", "1 + 2 + 3"),
+        ("add [add [1, 2], 3]", "(1 + 2) + 3"),
+        ("sub [sub [1, 2], 3]", "1 - 2 - 3"),
+        ("sub [1, sub [2, 3]]", "1 - (2 - 3)"),
+        ("or [not @this.a, and [gt [@this.b, 5], true]]", "!@this.a || @this.b > 5 && true"),
+        ("neg 5", "neg 5"),
+        ("neg add [1, 2]", "-(1 + 2)"),
+        ("abs neg @this.x", "abs (-@this.x)"),
+        ("sub [1]", "sub [1]"),
+    ];
+    for (source, expected) in cases {
+        assert_eq!(Pon::from_string(source).unwrap().to_string(), expected);
+    }
+}
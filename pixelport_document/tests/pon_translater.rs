@@ -5,14 +5,7 @@ use pixelport_document::*;
 use std::collections::HashMap;
 
 fn doc_function(ret_type: &str, matcher: PonDocMatcher) -> Vec<PonDocFunction> {
-    vec![PonDocFunction {
-        name: "testy".to_string(),
-        category: "test".to_string(),
-        module: "Test".to_string(),
-        doc: "Helps test".to_string(),
-        target_type_name: ret_type.to_string(),
-        arg: matcher
-    }]
+    vec![PonDocFunction::new("test", "Test", "testy", ret_type, matcher, "Helps test")]
 }
 
 #[test]
@@ -95,7 +88,7 @@ fn test_map_with_default() {
             PonDocMapField {
                 var_name: "thing".to_string(),
                 optional: false,
-                default: Some("4".to_string()),
+                default: Some("4.0".to_string()),
                 value: PonDocMatcher::Value {
                     typ: "f32".to_string()
                 }
@@ -240,3 +233,58 @@ fn test_nil() {
     let translater = PonTranslater::new();
     assert_eq!(translater.translate::<()>(&Pon::from_string("()").unwrap(), &mut bus).unwrap(), ());
 }
+
+#[test]
+fn test_integer_to_u64() {
+    let mut bus = Bus::new();
+    let translater = PonTranslater::new();
+    assert_eq!(translater.translate::<u64>(&Pon::from_string("16777217").unwrap(), &mut bus).unwrap(), 16777217);
+}
+
+#[test]
+fn test_integer_to_f32() {
+    let mut bus = Bus::new();
+    let translater = PonTranslater::new();
+    assert_eq!(translater.translate::<f32>(&Pon::from_string("5").unwrap(), &mut bus).unwrap(), 5.0);
+}
+
+#[test]
+fn test_inexact_integer_to_f32() {
+    let mut bus = Bus::new();
+    let translater = PonTranslater::new();
+    assert!(translater.translate::<f32>(&Pon::from_string("16777217").unwrap(), &mut bus).is_err());
+    assert_eq!(translater.translate::<f32>(&Pon::from_string("16777216").unwrap(), &mut bus).unwrap(), 16777216.0);
+}
+
+#[test]
+fn test_fractional_number_to_u64() {
+    let mut bus = Bus::new();
+    let translater = PonTranslater::new();
+    assert!(translater.translate::<u64>(&Pon::from_string("5.5").unwrap(), &mut bus).is_err());
+}
+
+#[test]
+fn test_string_to_selector() {
+    let mut bus = Bus::new();
+    let translater = PonTranslater::new();
+    assert_eq!(translater.translate::<Selector>(&Pon::from_string("'root'").unwrap(), &mut bus).unwrap(), Selector::root());
+}
+
+#[test]
+fn test_registered_coercion() {
+    let mut bus = Bus::new();
+    let mut translater = PonTranslater::new();
+    translater.register_coercion(|v: &bool| Some(if *v { "yes".to_string() } else { "no".to_string() }));
+    assert_eq!(translater.translate::<String>(&Pon::from_string("true").unwrap(), &mut bus).unwrap(), "yes".to_string());
+    assert!(translater.translate::<f32>(&Pon::from_string("true").unwrap(), &mut bus).is_err());
+}
+
+#[test]
+fn test_coercion_in_property_get() {
+    let mut translater = PonTranslater::new();
+    translater.register_coercion(|v: &bool| Some(if *v { "yes".to_string() } else { "no".to_string() }));
+    let doc = Document::from_string(translater, r#"<Entity name="tmp" flag="true" target="'root'" />"#).unwrap();
+    let ent = doc.get_entity_by_name("tmp").unwrap();
+    assert_eq!(doc.get_property::<String>(ent, "flag"), Ok("yes".to_string()));
+    assert_eq!(doc.get_property::<Selector>(ent, "target"), Ok(Selector::root()));
+}
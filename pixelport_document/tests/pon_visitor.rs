@@ -0,0 +1,117 @@
+extern crate pixelport_document;
+
+use pixelport_document::*;
+
+struct FunctionNames(Vec<String>);
+impl PonVisitor for FunctionNames {
+    fn visit_call(&mut self, call: &PonCall) {
+        self.0.push(call.function_name.clone());
+        walk_call(self, call);
+    }
+}
+
+fn function_names(source: &str) -> Vec<String> {
+    let mut names = FunctionNames(vec![]);
+    Pon::from_string(source).unwrap().visit(&mut names);
+    names.0.sort();
+    names.0
+}
+
+struct RenameFunction { from: String, to: String }
+impl PonFolder for RenameFunction {
+    type Error = ();
+    fn fold_call(&mut self, call: &mut PonCall) -> Result<(), ()> {
+        if call.function_name == self.from {
+            call.function_name = self.to.clone();
+        }
+        walk_call_mut(self, call)
+    }
+}
+
+struct RenameProperty { from: String, to: String }
+impl PonFolder for RenameProperty {
+    type Error = ();
+    fn fold_prop_ref(&mut self, named_prop_ref: &mut NamedPropRef) -> Result<(), ()> {
+        if named_prop_ref.property_key == self.from {
+            named_prop_ref.property_key = self.to.clone();
+        }
+        walk_named_prop_ref_mut(self, named_prop_ref)
+    }
+    fn fold_dep_prop_ref(&mut self, named_prop_ref: &mut NamedPropRef, resolved: &mut Option<PropRef>) -> Result<(), ()> {
+        self.fold_prop_ref(named_prop_ref)
+    }
+}
+
+fn fold<F: PonFolder>(source: &str, folder: &mut F) -> String {
+    let mut pon = Pon::from_string(source).unwrap();
+    pon.fold(folder).ok().unwrap();
+    pon.to_string()
+}
+
+#[test]
+fn test_collect_function_names() {
+    assert_eq!(function_names("vec3 { x: sin 1.0, y: [cos 2.0] }"), vec!["cos", "sin", "vec3"]);
+}
+
+#[test]
+fn test_collect_function_names_in_selectors() {
+    assert_eq!(function_names("@root:[x=(sin 1.0)].y"), vec!["sin"]);
+}
+
+#[test]
+fn test_rename_function() {
+    assert_eq!(fold("vec2 { x: scale [1, 2], y: scale [3, 4] }", &mut RenameFunction { from: "scale".to_string(), to: "times".to_string() }),
+        "vec2 { x: times [1, 2], y: times [3, 4] }");
+}
+
+#[test]
+fn test_rename_property() {
+    assert_eq!(fold("{ a: @this.x, b: this.x, c: @this.y }", &mut RenameProperty { from: "x".to_string(), to: "z".to_string() }),
+        "{ a: @this.z, b: this.z, c: @this.y }");
+}
+
+#[test]
+fn test_fold_replaces_nodes() {
+    struct Zero;
+    impl PonFolder for Zero {
+        type Error = ();
+        fn fold_pon(&mut self, pon: &mut Pon) -> Result<(), ()> {
+            if let &mut Pon::Number(_) = pon {
+                *pon = Pon::Number(0.0);
+            }
+            walk_pon_mut(self, pon)
+        }
+    }
+    assert_eq!(fold("[1.0, { a: 2.0 }, sin 3.0]", &mut Zero), "[0.0, { a: 0.0 }, sin 0.0]");
+}
+
+#[test]
+fn test_fold_error_aborts() {
+    struct NoCalls;
+    impl PonFolder for NoCalls {
+        type Error = String;
+        fn fold_call(&mut self, call: &mut PonCall) -> Result<(), String> {
+            Err(call.function_name.clone())
+        }
+    }
+    let mut pon = Pon::from_string("[1, sin 2.0]").unwrap();
+    assert_eq!(pon.fold(&mut NoCalls), Err("sin".to_string()));
+}
+
+#[test]
+fn test_build_dependencies_array() {
+    let mut pon = Pon::from_string("{ a: @this.x, b: [@this.y], c: this.z }").unwrap();
+    struct Resolve;
+    impl PonFolder for Resolve {
+        type Error = ();
+        fn fold_dep_prop_ref(&mut self, named_prop_ref: &mut NamedPropRef, resolved: &mut Option<PropRef>) -> Result<(), ()> {
+            *resolved = Some(PropRef::new(1, &named_prop_ref.property_key));
+            Ok(())
+        }
+    }
+    pon.fold(&mut Resolve).unwrap();
+    let mut references = vec![];
+    pon.build_dependencies_array(&mut references);
+    references.sort();
+    assert_eq!(references, vec![PropRef::new(1, "x"), PropRef::new(1, "y")]);
+}
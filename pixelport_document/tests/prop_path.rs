@@ -0,0 +1,88 @@
+#[macro_use]
+extern crate pixelport_document;
+extern crate cgmath;
+
+use pixelport_document::*;
+use cgmath::Vector3;
+
+fn translater() -> PonTranslater {
+    let mut translater = PonTranslater::new();
+    pon_register_functions!("test", "Test", translater =>
+        "Makes a vector",
+        vec3({ x: (f32), y: (f32), z: (f32), }) Vector3<f32> => { Ok(Vector3::new(x, y, z)) }
+    );
+    translater
+}
+
+#[test]
+fn test_parse_path() {
+    assert_eq!(NamedPropRef::from_string("this.points[2].y"), Ok(NamedPropRef::with_path(Selector::this(), "points",
+        vec![PropPathSegment::Index(2), PropPathSegment::Field("y".to_string())])));
+}
+
+#[test]
+fn test_parse_index_overflow() {
+    assert!(NamedPropRef::from_string("this.a[99999999999999999999999]").is_err());
+    assert!(Pon::from_string("@this.a[99999999999999999999999]").is_err());
+    assert!(Pon::from_string("#99999999999999999999999").is_err());
+}
+
+#[test]
+fn test_parse_quoted_field() {
+    assert_eq!(NamedPropRef::from_string("this.config.'max speed'").unwrap().path,
+        vec![PropPathSegment::Field("max speed".to_string())]);
+}
+
+#[test]
+fn test_parse_no_path() {
+    assert_eq!(NamedPropRef::from_string("this.points").unwrap().path, vec![]);
+}
+
+#[test]
+fn test_object_field() {
+    let doc = Document::from_string(translater(), r#"<Entity name="tmp" config="{ speed: 5.0 }" speed="@this.config.speed" />"#).unwrap();
+    let ent = doc.get_entity_by_name("tmp").unwrap();
+    assert_eq!(doc.get_property::<f32>(ent, "speed"), Ok(5.0));
+}
+
+#[test]
+fn test_array_element() {
+    let doc = Document::from_string(translater(), r#"<Entity name="tmp" points="[1.0, { y: 2.0 }]" y="@this.points[1].y" />"#).unwrap();
+    let ent = doc.get_entity_by_name("tmp").unwrap();
+    assert_eq!(doc.get_property::<f32>(ent, "y"), Ok(2.0));
+}
+
+#[test]
+fn test_translates_sub_value() {
+    let doc = Document::from_string(translater(), r#"<Entity name="tmp" config="{ pos: vec3 { x: 1.0, y: 2.0, z: 3.0 } }" pos="@this.config.pos" />"#).unwrap();
+    let ent = doc.get_entity_by_name("tmp").unwrap();
+    assert_eq!(doc.get_property::<Vector3<f32>>(ent, "pos"), Ok(Vector3::new(1.0, 2.0, 3.0)));
+}
+
+#[test]
+fn test_vector_component() {
+    let doc = Document::from_string(translater(), r#"<Entity name="tmp" pos="vec3 { x: 1.0, y: 2.0, z: 3.0 }" y="@this.pos.y" z="@this.pos[2]" />"#).unwrap();
+    let ent = doc.get_entity_by_name("tmp").unwrap();
+    assert_eq!(doc.get_property::<f32>(ent, "y"), Ok(2.0));
+    assert_eq!(doc.get_property::<f32>(ent, "z"), Ok(3.0));
+}
+
+#[test]
+fn test_invalid_path() {
+    let doc = Document::from_string(translater(), r#"<Entity name="tmp" pos="vec3 { x: 1.0, y: 2.0, z: 3.0 }" q="@this.pos.q" />"#).unwrap();
+    let ent = doc.get_entity_by_name("tmp").unwrap();
+    match doc.get_property::<f32>(ent, "q") {
+        Err(BusError::PonTranslateError { err: PonTranslaterErr::InvalidPropPath { segment, .. }, .. }) =>
+            assert_eq!(segment, PropPathSegment::Field("q".to_string())),
+        v => panic!("Expected an invalid path error, got {:?}", v)
+    }
+}
+
+#[test]
+fn test_depends_on_whole_property() {
+    let mut doc = Document::from_string(translater(), r#"<Entity name="tmp" config="{ speed: 5.0 }" speed="@this.config.speed" />"#).unwrap();
+    let ent = doc.get_entity_by_name("tmp").unwrap();
+    assert_eq!(doc.get_property::<f32>(ent, "speed"), Ok(5.0));
+    doc.set_property(ent, "config", Pon::from_string("{ speed: 7.0 }").unwrap(), false).unwrap();
+    assert_eq!(doc.get_property::<f32>(ent, "speed"), Ok(7.0));
+}
@@ -0,0 +1,39 @@
+extern crate pixelport_document;
+
+use pixelport_document::*;
+
+#[test]
+fn test_interned() {
+    assert_eq!(PropertyKey::new("some_key"), PropertyKey::new("some_key"));
+    assert!(PropertyKey::new("some_key") != PropertyKey::new("some_other_key"));
+    assert_eq!(PropertyKey::new("some_key").as_str(), "some_key");
+}
+
+#[test]
+fn test_ordered_by_string() {
+    let b = PropertyKey::new("order_b");
+    let a = PropertyKey::new("order_a");
+    assert!(a < b);
+    let mut prop_refs = vec![PropRef::new(2, "order_a"), PropRef::new(1, "order_b"), PropRef::new(1, "order_a")];
+    prop_refs.sort();
+    assert_eq!(prop_refs, vec![PropRef::new(1, "order_a"), PropRef::new(1, "order_b"), PropRef::new(2, "order_a")]);
+}
+
+#[test]
+fn test_formatting() {
+    let prop_ref = PropRef::new(5, "x");
+    assert_eq!(format!("{}.{}", prop_ref.entity_id, prop_ref.property_key), "5.x");
+    assert_eq!(format!("{:?}", prop_ref), r#"PropRef { entity_id: 5, property_key: "x" }"#);
+}
+
+#[test]
+fn test_interned_across_threads() {
+    let threads: Vec<_> = (0..4).map(|_| ::std::thread::spawn(|| {
+        (0..100).map(|i| PropertyKey::new(&format!("thread_key_{}", i))).collect::<Vec<_>>()
+    })).collect();
+    let keys: Vec<Vec<PropertyKey>> = threads.into_iter().map(|thread| thread.join().unwrap()).collect();
+    for other in &keys[1..] {
+        assert_eq!(&keys[0], other);
+    }
+    assert_eq!(keys[0][7].as_str(), "thread_key_7");
+}
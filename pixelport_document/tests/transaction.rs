@@ -0,0 +1,114 @@
+extern crate pixelport_document;
+
+use pixelport_document::*;
+
+fn channels_doc() -> Document {
+    let mut translater = PonTranslater::new();
+    DocumentChannels::pon_document_channels(&mut translater);
+    Document::from_string(translater, r#"<Root x="5.0"><Entity name="a" y="@root.x" /></Root>"#).unwrap()
+}
+
+fn request(doc: &mut Document, channels: &mut DocumentChannels, message: &str) -> String {
+    let inc = IncomingMessage::from_string(&doc.translater, &mut doc.bus, ClientId::CAPI, "1".to_string(), message).unwrap();
+    let mut out = vec![];
+    assert!(channels.handle_request(&inc, &mut out, doc));
+    assert_eq!(out.len(), 1);
+    out[0].to_tcpmessage()
+}
+
+#[test]
+fn test_rollback() {
+    let mut doc = Document::from_string(PonTranslater::new(), r#"<Root x="5.0"><Entity name="a" y="@root.x" /></Root>"#).unwrap();
+    doc.close_cycle();
+    let root = doc.get_root().unwrap();
+    let a = doc.get_entity_by_name("a").unwrap();
+    doc.begin_transaction().unwrap();
+    let b = doc.append_entity(None, Some(root), "Entity", Some("b".to_string())).unwrap();
+    doc.set_property(b, "z", Pon::from_string("@root.x").unwrap(), false).unwrap();
+    doc.set_property(root, "x", Pon::Number(7.0), false).unwrap();
+    doc.remove_entity(a).unwrap();
+    assert_eq!(doc.get_property::<f32>(b, "z"), Ok(7.0));
+    doc.rollback_transaction().unwrap();
+
+    assert_eq!(doc.get_entity_by_name("b"), None);
+    assert!(!doc.has_property(b, "z"));
+    assert_eq!(doc.get_children(root).unwrap(), &vec![a]);
+    assert_eq!(doc.get_property::<f32>(a, "y"), Ok(5.0));
+    assert_eq!(doc.bus.get_dependents(&PropRef::new(root, "x"), false), vec![PropRef::new(a, "y")]);
+    let changes = doc.close_cycle();
+    assert!(!changes.changed());
+}
+
+#[test]
+fn test_rollback_after_closing_cycle() {
+    let mut doc = Document::from_string(PonTranslater::new(), r#"<Root x="5.0" />"#).unwrap();
+    doc.close_cycle();
+    let root = doc.get_root().unwrap();
+    let a = doc.append_entity(None, Some(root), "Entity", None).unwrap();
+    doc.begin_transaction().unwrap();
+    doc.append_entity(None, Some(root), "Entity", None).unwrap();
+    doc.set_property(root, "x", Pon::Number(7.0), false).unwrap();
+    doc.close_cycle();
+    doc.rollback_transaction().unwrap();
+
+    assert_eq!(doc.get_children(root).unwrap(), &vec![a]);
+    assert_eq!(doc.get_property::<f32>(root, "x"), Ok(5.0));
+    let changes = doc.close_cycle();
+    assert_eq!(changes.entities_added, vec![a]);
+    assert_eq!(changes.invalidations_log, vec![]);
+}
+
+#[test]
+fn test_commit() {
+    let mut doc = Document::from_string(PonTranslater::new(), r#"<Root x="5.0" />"#).unwrap();
+    let root = doc.get_root().unwrap();
+    doc.begin_transaction().unwrap();
+    doc.set_property(root, "x", Pon::Number(7.0), false).unwrap();
+    doc.commit_transaction().unwrap();
+    assert_eq!(doc.rollback_transaction(), Err(DocError::NoTransaction));
+    assert_eq!(doc.get_property::<f32>(root, "x"), Ok(7.0));
+}
+
+#[test]
+fn test_nested_transaction() {
+    let mut doc = Document::new_with_root(PonTranslater::new());
+    doc.begin_transaction().unwrap();
+    assert_eq!(doc.begin_transaction(), Err(DocError::TransactionInProgress));
+}
+
+#[test]
+fn test_transaction_closure_rolls_back_on_error() {
+    let mut doc = Document::from_string(PonTranslater::new(), r#"<Root x="5.0" />"#).unwrap();
+    let root = doc.get_root().unwrap();
+    let res: Result<(), DocError> = doc.transaction(|doc| {
+        try!(doc.set_property(root, "x", Pon::Number(7.0), false));
+        try!(doc.set_property(root, "x", Pon::from_string("@this.x").unwrap(), false));
+        Ok(())
+    });
+    assert!(res.is_err());
+    assert!(!doc.in_transaction());
+    assert_eq!(doc.get_property::<f32>(root, "x"), Ok(5.0));
+}
+
+#[test]
+fn test_batch() {
+    let mut doc = channels_doc();
+    let mut channels = DocumentChannels::new();
+    let root = doc.get_root().unwrap();
+    let response = request(&mut doc, &mut channels,
+        "batch { requests: [set_properties { entity: root, properties: { x: 7.0 } }, append_entity { entity_id: 100, parent: root, type_name: 'Entity', properties: {} }] }");
+    assert_eq!(response, "1 ok [(), 100]");
+    assert_eq!(doc.get_property::<f32>(root, "x"), Ok(7.0));
+    assert_eq!(doc.get_entity_type_name(100), Ok("Entity".to_string()));
+}
+
+#[test]
+fn test_batch_rolls_back_on_failure() {
+    let mut doc = channels_doc();
+    let mut channels = DocumentChannels::new();
+    let root = doc.get_root().unwrap();
+    let response = request(&mut doc, &mut channels,
+        "batch { requests: [set_properties { entity: root, properties: { x: 7.0 } }, remove_entity { entity: root:missing }] }");
+    assert!(response.starts_with("1 err "));
+    assert_eq!(doc.get_property::<f32>(root, "x"), Ok(5.0));
+}
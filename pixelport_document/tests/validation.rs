@@ -0,0 +1,105 @@
+#[macro_use]
+extern crate pixelport_document;
+
+use pixelport_document::*;
+
+fn translater() -> PonTranslater {
+    let mut translater = PonTranslater::new();
+    pon_register_functions!("test", "Test", translater =>
+        "Doubles a number",
+        double(value: (f32)) f32 => { Ok(value * 2.0) }
+
+        "Makes a greeting",
+        greet({ name: (String), punctuation: (String) | "!", title: (String) optional, }) String => {
+            Ok(format!("Hello {}{}", name, punctuation))
+        }
+
+        "Picks a size",
+        size(value: (enum { "small" => 1.0, "large" => 10.0, })) f32 => { Ok(value) }
+
+        "Sums numbers",
+        sum(values: [f32]) f32 => { Ok(values.iter().fold(0.0, |a, b| a + b)) }
+    );
+    translater
+}
+
+fn validate(xml: &str) -> Vec<ValidationErrorKind> {
+    let doc = Document::from_string(translater(), xml).unwrap();
+    doc.validate().into_iter().map(|error| error.kind).collect()
+}
+
+#[test]
+fn test_valid_document() {
+    assert_eq!(validate(r#"<Entity x="double 5" y="greet { name: 'world' }" z="size 'large'" w="sum [1, 2.5, double @this.x]" />"#), vec![]);
+}
+
+#[test]
+fn test_unknown_function() {
+    assert_eq!(validate(r#"<Entity x="double (triple 5)" />"#), vec![
+        ValidationErrorKind::UnknownFunction { function_name: "triple".to_string() }
+    ]);
+}
+
+#[test]
+fn test_missing_field() {
+    assert_eq!(validate(r#"<Entity x="greet { title: 'dr' }" />"#), vec![
+        ValidationErrorKind::MissingField { function_name: "greet".to_string(), field: "name".to_string() }
+    ]);
+}
+
+#[test]
+fn test_wrong_enum_value() {
+    assert_eq!(validate(r#"<Entity x="size 'medium'" />"#), vec![
+        ValidationErrorKind::WrongEnumValue {
+            function_name: "size".to_string(),
+            expected_one_of: vec!["small".to_string(), "large".to_string()],
+            found: "medium".to_string()
+        }
+    ]);
+}
+
+#[test]
+fn test_wrong_literal_type() {
+    assert_eq!(validate(r#"<Entity x="double 'five'" />"#), vec![
+        ValidationErrorKind::WrongType {
+            function_name: "double".to_string(),
+            expected_type: "f32".to_string(),
+            found_type: "String".to_string(),
+            found_value: "'five'".to_string()
+        }
+    ]);
+}
+
+#[test]
+fn test_wrong_dependency_type() {
+    assert_eq!(validate(r#"<Entity x="greet { name: 'world' }" y="double @this.x" />"#), vec![
+        ValidationErrorKind::WrongType {
+            function_name: "double".to_string(),
+            expected_type: "f32".to_string(),
+            found_type: "String".to_string(),
+            found_value: "@this.x".to_string()
+        }
+    ]);
+}
+
+#[test]
+fn test_integers_are_numbers() {
+    assert_eq!(validate(r#"<Entity x="double 5" y="sum [1, 2, 3]" />"#), vec![]);
+}
+
+#[test]
+fn test_errors_have_prop_refs() {
+    let doc = Document::from_string(translater(), r#"<Entity name="a" x="nope 5" />"#).unwrap();
+    let ent = doc.get_entity_by_name("a").unwrap();
+    let errors = doc.validate();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].prop_ref, PropRef::new(ent, "x"));
+}
+
+#[test]
+fn test_coerced_types_are_accepted() {
+    let mut translater = translater();
+    translater.register_coercion(|v: &bool| Some(if *v { 1.0f32 } else { 0.0 }));
+    let doc = Document::from_string(translater, r#"<Entity x="double true" />"#).unwrap();
+    assert_eq!(doc.validate(), vec![]);
+}
@@ -270,6 +270,22 @@ $ export PIXELPORT_APP_PATH=~/pixelport/pixelport_app/target/release/pixelport_a
   static ponEscape(str) {
     return str.replace(/\\/g, "\\\\").replace(/'/g, "\\'");
   }
+  // Escapes like the Rust side does, so that strings parse back to exactly the same string
+  static stringifyPonString(str) {
+    return "'" + str.replace(/[\\'\n\r\t]|[\u0000-\u001f\u007f-\u009f]/g, c => {
+      switch (c) {
+        case "\\": return "\\\\";
+        case "'": return "\\'";
+        case "\n": return "\\n";
+        case "\r": return "\\r";
+        case "\t": return "\\t";
+        default: return "\\u{" + c.charCodeAt(0).toString(16) + "}";
+      }
+    }) + "'";
+  }
+  static stringifyPonKey(key) {
+    return /^[a-zA-Z_][a-zA-Z_0-9]*$/.test(key) ? key : Pixelport.stringifyPonString(key);
+  }
   static ponUnescape(str) {
     return str.replace(/\\\\/g, "\\").replace(/\\'/g, "'");
   }
@@ -285,6 +301,8 @@ $ export PIXELPORT_APP_PATH=~/pixelport/pixelport_app/target/release/pixelport_a
       return pon.propref;
     } else if (pon instanceof ponTypes.PonDepPropRef) {
       return "@" + pon.propref;
+    } else if (pon instanceof ponTypes.PonArgument) {
+      return "$" + pon.name;
     } else if (pon instanceof ponTypes.PonSelector) {
       return pon.selector;
     } else if (Array.isArray(pon)) {
@@ -292,9 +310,11 @@ $ export PIXELPORT_APP_PATH=~/pixelport/pixelport_app/target/release/pixelport_a
     } else if (pon instanceof Object) {
       return '{ ' + Object.keys(pon)
         .filter(k => pon[k] !== null)
-        .map(k => k + ': ' + Pixelport.stringifyPon(pon[k])).join(', ') + ' }';
+        .map(k => Pixelport.stringifyPonKey(k) + ': ' + Pixelport.stringifyPon(pon[k])).join(', ') + ' }';
     } else if (typeof pon === 'string') {
-      return "'" + pon + "'";
+      return Pixelport.stringifyPonString(pon);
+    } else if (typeof pon === 'number' && !isFinite(pon)) {
+      return isNaN(pon) ? "NaN" : (pon > 0 ? "inf" : "-inf");
     } else {
       return "" + pon;
     }
@@ -313,6 +333,7 @@ $ export PIXELPORT_APP_PATH=~/pixelport/pixelport_app/target/release/pixelport_a
 Pixelport.PonCall = ponTypes.PonCall;
 Pixelport.PonPropRef = ponTypes.PonPropRef;
 Pixelport.PonDepPropRef = ponTypes.PonDepPropRef;
+Pixelport.PonArgument = ponTypes.PonArgument;
 Pixelport.PonSelector = ponTypes.PonSelector;
 
 module.exports = Pixelport;
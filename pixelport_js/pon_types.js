@@ -19,6 +19,12 @@ class PonDepPropRef {
   }
 }
 
+class PonArgument {
+  constructor(name) {
+    this.name = name;
+  }
+}
+
 class PonSelector {
   constructor(selector) {
     this.selector = selector;
@@ -29,4 +35,5 @@ class PonSelector {
 module.exports.PonCall = PonCall;
 module.exports.PonPropRef = PonPropRef;
 module.exports.PonDepPropRef = PonDepPropRef;
+module.exports.PonArgument = PonArgument;
 module.exports.PonSelector = PonSelector;
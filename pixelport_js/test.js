@@ -7,6 +7,7 @@ var PonCall = require('./pon_types').PonCall;
 var PonDepPropRef = require('./pon_types').PonDepPropRef;
 var PonPropRef = require('./pon_types').PonPropRef;
 var PonSelector = require('./pon_types').PonSelector;
+var PonArgument = require('./pon_types').PonArgument;
 
 suite('request', function() {
 
@@ -66,6 +67,20 @@ suite('pon parsing', function() {
     assert.deepEqual(Pixelport.parsePon("test { x: 'hello' }"), new PonCall("test", { x: "hello" }));
   });
 
+  test('string escapes', function() {
+    assert.deepEqual(Pixelport.parsePon("'it\\'s\\n\\t\\\\ \\u{1f600}\\u{7}'"), "it's\n\t\\ \u{1f600}\u0007");
+  });
+
+  test('quoted keys', function() {
+    assert.deepEqual(Pixelport.parsePon("{ 'a b': 1, '1st': 2, c: 3 }"), { "a b": 1, "1st": 2, c: 3 });
+  });
+
+  test('non-finite numbers', function() {
+    assert.deepEqual(Pixelport.parsePon("[inf, -inf]"), [Infinity, -Infinity]);
+    assert.isTrue(isNaN(Pixelport.parsePon("NaN")));
+    assert.deepEqual(Pixelport.parsePon("info"), new PonSelector("info"));
+  });
+
   test('selector', function() {
     assert.deepEqual(Pixelport.parsePon("test { x: #15 }"), new PonCall("test", { x: new PonSelector("#15") }));
   });
@@ -78,6 +93,16 @@ suite('pon parsing', function() {
     assert.deepEqual(Pixelport.parsePon("test { x: @root:Hello.y }"), new PonCall("test", { x: new PonDepPropRef("root:Hello.y") }));
   });
 
+  test('argument', function() {
+    assert.deepEqual(Pixelport.parsePon("test { x: $suit }"), new PonCall("test", { x: new PonArgument("suit") }));
+  });
+
+  test('dep_propref_path', function() {
+    assert.deepEqual(Pixelport.parsePon("@root.camera_pos.x"), new PonDepPropRef("root.camera_pos.x"));
+    assert.deepEqual(Pixelport.parsePon("@this.points[2].y"), new PonDepPropRef("this.points[2].y"));
+    assert.deepEqual(Pixelport.parsePon("@this.config.'max speed'"), new PonDepPropRef("this.config.'max speed'"));
+  });
+
   test('array', function() {
     assert.deepEqual(Pixelport.parsePon("test { x: [5, 3] }"), new PonCall("test", { x: [5, 3] }));
   });
@@ -89,6 +114,16 @@ suite('pon parsing', function() {
   test('boolean', function() {
     assert.deepEqual(Pixelport.parsePon("test { x: true }"), new PonCall("test", { x: true }));
   });
+
+  test('infix', function() {
+    assert.deepEqual(Pixelport.parsePon("@this.a * 2 + 1"),
+      new PonCall("add", [new PonCall("mul", [new PonDepPropRef("this.a"), 2]), 1]));
+  });
+
+  test('infix parentheses', function() {
+    assert.deepEqual(Pixelport.parsePon("-(1 + 2 + 3) < 4"),
+      new PonCall("lt", [new PonCall("neg", new PonCall("add", [1, 2, 3])), 4]));
+  });
 });
 
 suite('pon stringify', function() {
@@ -97,6 +132,20 @@ suite('pon stringify', function() {
     assert.deepEqual(Pixelport.stringifyPon("test"), "'test'");
   });
 
+  test('string escapes', function() {
+    let str = "it's\n\\ \u0007";
+    assert.deepEqual(Pixelport.stringifyPon(str), "'it\\'s\\n\\\\ \\u{7}'");
+    assert.deepEqual(Pixelport.parsePon(Pixelport.stringifyPon(str)), str);
+  });
+
+  test('quoted keys', function() {
+    assert.deepEqual(Pixelport.stringifyPon({ "a b": 1, c: 2 }), "{ 'a b': 1, c: 2 }");
+  });
+
+  test('non-finite numbers', function() {
+    assert.deepEqual(Pixelport.stringifyPon([NaN, Infinity, -Infinity]), "[ NaN, inf, -inf ]");
+  });
+
   test('propref', function() {
     assert.deepEqual(Pixelport.stringifyPon(new PonCall("test", { x: new PonPropRef("#15.5") })), "test { x: #15.5 }");
   });
@@ -109,6 +158,10 @@ suite('pon stringify', function() {
     assert.deepEqual(Pixelport.stringifyPon(new PonCall("test", { x: new PonSelector("#15") })), "test { x: #15 }");
   });
 
+  test('argument', function() {
+    assert.deepEqual(Pixelport.stringifyPon(new PonCall("test", { x: new PonArgument("suit") })), "test { x: $suit }");
+  });
+
   test('nil', function() {
     assert.deepEqual(Pixelport.stringifyPon(new PonCall("test", null)), "test ()");
   });
@@ -13,18 +13,40 @@ use std::f32;
 
 use std::hash::Hasher;
 use std::hash::Hash;
+use std::collections::HashMap;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub struct Rectangle {
     pub x: f32,
     pub y: f32,
     pub width: f32,
     pub height: f32
 }
+impl Rectangle {
+    fn canonical_bits(&self) -> [u32; 4] {
+        [canonical_f32_bits(self.x), canonical_f32_bits(self.y),
+         canonical_f32_bits(self.width), canonical_f32_bits(self.height)]
+    }
+}
+impl PartialEq for Rectangle {
+    fn eq(&self, other: &Rectangle) -> bool {
+        self.canonical_bits() == other.canonical_bits()
+    }
+}
+impl Eq for Rectangle {}
+impl ToPon for Rectangle {
+    fn to_pon(&self) -> Pon {
+        Pon::call("rectangle", Pon::Object(hashmap![
+            "x" => self.x.to_pon(),
+            "y" => self.y.to_pon(),
+            "width" => self.width.to_pon(),
+            "height" => self.height.to_pon()
+        ]))
+    }
+}
 impl Hash for Rectangle {
     fn hash<H>(&self, state: &mut H) where H: Hasher {
-        let str = format!("{:?}", self);
-        str.hash(state);
+        self.canonical_bits().hash(state);
     }
 }
 
@@ -165,11 +187,181 @@ pub fn construct_shadow_camera(decomposed_camera: &DecomposedCamera, cascade_ind
     return &ortho(min.x, max.x, min.y, max.y, -max.z, -min.z) * shadow_view;
 }
 
+// `if` and `select` evaluate to whatever their chosen value translates to, so they're registered
+// without pon_register_functions!, which needs a concrete return type. Only the chosen value is
+// translated.
+
+fn pon_if(arg: &Pon, translater: &PonTranslater, bus: &Bus) -> Result<Box<BusValue>, PonTranslaterErr> {
+    let fields = try!(translater.translate::<HashMap<String, Pon>>(arg, bus));
+    let condition = match fields.get("condition") {
+        Some(condition) => try!(translater.translate::<bool>(condition, bus)),
+        None => return Err(PonTranslaterErr::RequiredFieldMissing { field: "condition".to_string() })
+    };
+    let branch = if condition { "then" } else { "else" };
+    match fields.get(branch) {
+        Some(value) => translater.translate_raw(value, bus),
+        None => Err(PonTranslaterErr::RequiredFieldMissing { field: branch.to_string() })
+    }
+}
+
+// Numbers with no fractional part use the same key as the integer, so 2.0 selects case '2'
+fn select_key(value: &Box<BusValue>) -> Option<String> {
+    if let Some(v) = (**value).downcast_ref::<String>() {
+        Some(v.clone())
+    } else if let Some(v) = (**value).downcast_ref::<i64>() {
+        Some(v.to_string())
+    } else if let Some(v) = (**value).downcast_ref::<u64>() {
+        Some(v.to_string())
+    } else if let Some(v) = (**value).downcast_ref::<f32>() {
+        if v.fract() == 0.0 && v.is_finite() {
+            Some((*v as i64).to_string())
+        } else {
+            Some(v.to_string())
+        }
+    } else if let Some(v) = (**value).downcast_ref::<bool>() {
+        Some(v.to_string())
+    } else {
+        None
+    }
+}
+
+fn pon_select(arg: &Pon, translater: &PonTranslater, bus: &Bus) -> Result<Box<BusValue>, PonTranslaterErr> {
+    let fields = try!(translater.translate::<HashMap<String, Pon>>(arg, bus));
+    let key = match fields.get("value") {
+        Some(value) => match select_key(&try!(translater.translate_raw(value, bus))) {
+            Some(key) => key,
+            None => return Err(PonTranslaterErr::ValueOfUnexpectedType {
+                expected_type: "a string, number or bool".to_string(),
+                found_value: value.to_string()
+            })
+        },
+        None => return Err(PonTranslaterErr::RequiredFieldMissing { field: "value".to_string() })
+    };
+    let cases = match fields.get("cases") {
+        Some(cases) => try!(translater.translate::<HashMap<String, Pon>>(cases, bus)),
+        None => return Err(PonTranslaterErr::RequiredFieldMissing { field: "cases".to_string() })
+    };
+    match cases.get(&key).or(fields.get("default")) {
+        Some(value) => translater.translate_raw(value, bus),
+        None => Err(PonTranslaterErr::Generic(format!("No case for {} and no default", key)))
+    }
+}
+
+// `eq` and `neq` compare values of any type, so they're registered like `if`. Integers and numbers
+// compare by value, so `5 == 5.0`.
+
+fn as_number(value: &Box<BusValue>) -> Option<f64> {
+    if let Some(v) = (**value).downcast_ref::<f32>() {
+        Some(*v as f64)
+    } else if let Some(v) = (**value).downcast_ref::<i64>() {
+        Some(*v as f64)
+    } else if let Some(v) = (**value).downcast_ref::<u64>() {
+        Some(*v as f64)
+    } else {
+        None
+    }
+}
+
+fn values_equal(arg: &Pon, translater: &PonTranslater, bus: &Bus) -> Result<bool, PonTranslaterErr> {
+    let vals = try!(translater.translate::<Vec<Pon>>(arg, bus));
+    if vals.len() != 2 {
+        return Err(PonTranslaterErr::Generic(format!("Expected two values to compare, found {}", vals.len())));
+    }
+    let a = try!(translater.translate_raw(&vals[0], bus));
+    let b = try!(translater.translate_raw(&vals[1], bus));
+    Ok(match (as_number(&a), as_number(&b)) {
+        (Some(a), Some(b)) => a == b,
+        _ => (*a).bus_value_equals(&b)
+    })
+}
+
+fn pon_eq(arg: &Pon, translater: &PonTranslater, bus: &Bus) -> Result<Box<BusValue>, PonTranslaterErr> {
+    Ok(Box::new(try!(values_equal(arg, translater, bus))))
+}
+
+fn pon_neq(arg: &Pon, translater: &PonTranslater, bus: &Bus) -> Result<Box<BusValue>, PonTranslaterErr> {
+    Ok(Box::new(!try!(values_equal(arg, translater, bus))))
+}
+
+// Operands of the binary number functions, like the operands of `values_equal`.
+fn two_numbers(vals: &[f32]) -> Result<(f32, f32), PonTranslaterErr> {
+    if vals.len() != 2 {
+        return Err(PonTranslaterErr::Generic(format!("Expected two values, found {}", vals.len())));
+    }
+    Ok((vals[0], vals[1]))
+}
+
+// Numbers that are whole and fit in the target type can be used as integers, like `convert_number`
+// does for i64 and u64.
+fn whole_number(v: f32, min: f64, max: f64) -> Option<f64> {
+    let v = v as f64;
+    if v.fract() == 0.0 && v.is_finite() && v >= min && v <= max {
+        Some(v)
+    } else {
+        None
+    }
+}
+
+fn register_coercions(translater: &mut PonTranslater) {
+    translater.register_coercion(|v: &Vector2<f32>| Some(Vector3::new(v.x, v.y, 0.0)));
+    translater.register_coercion(|v: &Vector3<f32>| Some(Vector2::new(v.x, v.y)));
+    translater.register_coercion(|v: &Vector3<f32>| Some(Vector4::new(v.x, v.y, v.z, 1.0)));
+    translater.register_coercion(|v: &Vector4<f32>| Some(Vector3::new(v.x, v.y, v.z)));
+    translater.register_coercion(|v: &String| hex_to_color(v.trim_left_matches('#')));
+
+    translater.register_coercion(|v: &f32| Some(*v as f64));
+    translater.register_coercion(|v: &i64| Some(*v as f64));
+    translater.register_coercion(|v: &f32| whole_number(*v, 0.0, usize::max_value() as f64).map(|v| v as usize));
+    translater.register_coercion(|v: &i64| if *v >= 0 { Some(*v as usize) } else { None });
+    translater.register_coercion(|v: &f32| whole_number(*v, i32::min_value() as f64, i32::max_value() as f64).map(|v| v as i32));
+    translater.register_coercion(|v: &i64| if *v >= i32::min_value() as i64 && *v <= i32::max_value() as i64 { Some(*v as i32) } else { None });
+    translater.register_coercion(|v: &f32| whole_number(*v, 0.0, u32::max_value() as f64).map(|v| v as u32));
+    translater.register_coercion(|v: &i64| if *v >= 0 && *v <= u32::max_value() as i64 { Some(*v as u32) } else { None });
+}
 
 pub fn pon_std(translater: &mut PonTranslater) {
+    register_coercions(translater);
+    translater.register_to_pon::<Rectangle>();
+    translater.register_function(pon_if, PonDocFunction {
+        category: "document".to_string(),
+        module: "Standard Library".to_string(),
+        name: "if".to_string(),
+        target_type_name: "Pon".to_string(),
+        arg: pon_doc_expand!({ condition: (bool), then: (Pon), else: (Pon), }),
+        doc: "Return then or else depending on condition. The values can be of any type, and only the chosen one is evaluated".to_string(),
+        pure: true
+    });
+    translater.register_function(pon_select, PonDocFunction {
+        category: "document".to_string(),
+        module: "Standard Library".to_string(),
+        name: "select".to_string(),
+        target_type_name: "Pon".to_string(),
+        arg: pon_doc_expand!({ value: (Pon), cases: { Pon }, default: (Pon) optional, }),
+        doc: "Return the case whose key is value, or default if there is no such case. Value can be a string, number or bool, cases can be of any type and only the chosen one is evaluated".to_string(),
+        pure: true
+    });
+    translater.register_function(pon_eq, PonDocFunction {
+        category: "document".to_string(),
+        module: "Standard Library".to_string(),
+        name: "eq".to_string(),
+        target_type_name: "bool".to_string(),
+        arg: pon_doc_expand!(vals: [Pon]),
+        doc: "Check if two values are equal. The values can be of any type, integers and numbers are compared by value".to_string(),
+        pure: true
+    });
+    translater.register_function(pon_neq, PonDocFunction {
+        category: "document".to_string(),
+        module: "Standard Library".to_string(),
+        name: "neq".to_string(),
+        target_type_name: "bool".to_string(),
+        arg: pon_doc_expand!(vals: [Pon]),
+        doc: "Check if two values are not equal. The values can be of any type, integers and numbers are compared by value".to_string(),
+        pure: true
+    });
     pon_register_functions!("document", "Standard Library", translater =>
 
         "Generate random float",
+        #[impure]
         random_float() f32 => {
             Ok(rand::random::<f32>())
         }
@@ -186,7 +378,8 @@ pub fn pon_std(translater: &mut PonTranslater) {
 
         "Subtract two numbers",
         sub(vals: [f32]) f32 => {
-            Ok(vals[0] - vals[1])
+            let (a, b) = try!(two_numbers(&vals));
+            Ok(a - b)
         }
 
         "Multiply a list of numbers",
@@ -199,6 +392,36 @@ pub fn pon_std(translater: &mut PonTranslater) {
             Ok(-val)
         }
 
+        "Remainder of dividing two numbers",
+        modulo(vals: [f32]) f32 => {
+            let (a, b) = try!(two_numbers(&vals));
+            Ok(a % b)
+        }
+
+        "Check if a number is less than another",
+        lt(vals: [f32]) bool => {
+            let (a, b) = try!(two_numbers(&vals));
+            Ok(a < b)
+        }
+
+        "Check if a number is less than or equal to another",
+        lte(vals: [f32]) bool => {
+            let (a, b) = try!(two_numbers(&vals));
+            Ok(a <= b)
+        }
+
+        "Check if a number is greater than another",
+        gt(vals: [f32]) bool => {
+            let (a, b) = try!(two_numbers(&vals));
+            Ok(a > b)
+        }
+
+        "Check if a number is greater than or equal to another",
+        gte(vals: [f32]) bool => {
+            let (a, b) = try!(two_numbers(&vals));
+            Ok(a >= b)
+        }
+
         "Pi.",
         pi() f32 => {
             Ok(std::f32::consts::PI)
@@ -532,3 +755,148 @@ fn test_translate_matrix_mul() {
     assert_eq!(doc.translater.translate::<Matrix4<f32>>(&pon, &doc.bus),
         Ok(Matrix4::from_translation(Vector3::new(2.0, -4.0, 0.0))));
 }
+
+#[test]
+fn test_translate_infix_arithmetic() {
+    let mut translater = PonTranslater::new();
+    pon_std(&mut translater);
+    let doc = Document::new(translater);
+    let pon = Pon::from_string("-(2 * 3 + 1) % 4 - 10 / 4").unwrap();
+    assert_eq!(doc.translater.translate::<f32>(&pon, &doc.bus), Ok(-5.5));
+}
+
+#[test]
+fn test_translate_infix_logic() {
+    let mut translater = PonTranslater::new();
+    pon_std(&mut translater);
+    let doc = Document::new(translater);
+    let pon = Pon::from_string("1 < 2 && !(3 >= 4) || false").unwrap();
+    assert_eq!(doc.translater.translate::<bool>(&pon, &doc.bus), Ok(true));
+}
+
+#[test]
+fn test_translate_equality() {
+    let mut translater = PonTranslater::new();
+    pon_std(&mut translater);
+    let mut doc = Document::from_string(translater, r#"<Entity name="tmp" mode="'run'" a="5" />"#).unwrap();
+    let ent = doc.get_entity_by_name("tmp").unwrap();
+    for &(expression, expected) in &[("@this.mode == 'run'", true), ("@this.mode != 'run'", false),
+                                     ("@this.a == 5.0", true), ("true == false", false),
+                                     ("[1, 'a'] == [1, 'a']", true), ("'5' == 5", false)] {
+        doc.set_property(ent, "test", Pon::from_string(expression).unwrap(), false).unwrap();
+        assert_eq!((expression, doc.get_property::<bool>(ent, "test")), (expression, Ok(expected)));
+    }
+}
+
+#[test]
+fn test_binary_number_functions_expect_two_values() {
+    let mut translater = PonTranslater::new();
+    pon_std(&mut translater);
+    let doc = Document::new(translater);
+    for expression in &["lt [1]", "gte [1, 2, 3]", "modulo []", "sub [1]"] {
+        let pon = Pon::from_string(expression).unwrap();
+        assert!(doc.translater.translate_raw(&pon, &doc.bus).is_err(), "{} should fail", expression);
+    }
+}
+
+#[test]
+fn test_infix_dependencies() {
+    let mut translater = PonTranslater::new();
+    pon_std(&mut translater);
+    let mut doc = Document::from_string(translater, r#"<Entity name="tmp" a="5.0" b="@this.a * 2 + 1" />"#).unwrap();
+    let ent = doc.get_entity_by_name("tmp").unwrap();
+    assert_eq!(doc.get_property::<f32>(ent, "b").unwrap(), 11.0);
+    doc.set_property(ent, "a", Pon::Number(1.0), false).unwrap();
+    assert_eq!(doc.get_property::<f32>(ent, "b").unwrap(), 3.0);
+}
+
+#[test]
+fn test_if() {
+    let mut translater = PonTranslater::new();
+    pon_std(&mut translater);
+    let doc = Document::new(translater);
+    let pon = Pon::from_string("if { condition: 1 < 2, then: 'yes', else: 'no' }").unwrap();
+    assert_eq!(doc.translater.translate::<String>(&pon, &doc.bus), Ok("yes".to_string()));
+    let pon = Pon::from_string("if { condition: false, then: vec3 { x: 1 }, else: vec3 { y: 1 } }").unwrap();
+    assert_eq!(doc.translater.translate::<Vector3<f32>>(&pon, &doc.bus), Ok(Vector3::new(0.0, 1.0, 0.0)));
+}
+
+#[test]
+fn test_if_is_lazy() {
+    let mut translater = PonTranslater::new();
+    pon_std(&mut translater);
+    let doc = Document::new(translater);
+    let pon = Pon::from_string("if { condition: true, then: 5.0, else: no_such_function () }").unwrap();
+    assert_eq!(doc.translater.translate::<f32>(&pon, &doc.bus), Ok(5.0));
+}
+
+#[test]
+fn test_select() {
+    let mut translater = PonTranslater::new();
+    pon_std(&mut translater);
+    let doc = Document::new(translater);
+    let pon = Pon::from_string("select { value: 'run', cases: { walk: 1.0, run: 2.0 }, default: 0.0 }").unwrap();
+    assert_eq!(doc.translater.translate::<f32>(&pon, &doc.bus), Ok(2.0));
+    let pon = Pon::from_string("select { value: 'jump', cases: { walk: 1.0, run: 2.0 }, default: 0.0 }").unwrap();
+    assert_eq!(doc.translater.translate::<f32>(&pon, &doc.bus), Ok(0.0));
+}
+
+#[test]
+fn test_select_number() {
+    let mut translater = PonTranslater::new();
+    pon_std(&mut translater);
+    let doc = Document::new(translater);
+    let pon = Pon::from_string("select { value: 1 + 1.0, cases: { '1': 'one', '2': 'two' } }").unwrap();
+    assert_eq!(doc.translater.translate::<String>(&pon, &doc.bus), Ok("two".to_string()));
+}
+
+#[test]
+fn test_select_dependency() {
+    let mut translater = PonTranslater::new();
+    pon_std(&mut translater);
+    let mut doc = Document::from_string(translater, r#"<Entity name="tmp" mode="'a'" v="select { value: @this.mode, cases: { a: 1.0, b: 2.0 } }" />"#).unwrap();
+    let ent = doc.get_entity_by_name("tmp").unwrap();
+    assert_eq!(doc.get_property::<f32>(ent, "v").unwrap(), 1.0);
+    doc.set_property(ent, "mode", Pon::String("b".to_string()), false).unwrap();
+    assert_eq!(doc.get_property::<f32>(ent, "v").unwrap(), 2.0);
+}
+
+#[test]
+fn test_coerce_vectors() {
+    let mut translater = PonTranslater::new();
+    pon_std(&mut translater);
+    let doc = Document::new(translater);
+    let pon = Pon::from_string("vec3 { x: 1, y: 2, z: 3 }").unwrap();
+    assert_eq!(doc.translater.translate::<Vector4<f32>>(&pon, &doc.bus), Ok(Vector4::new(1.0, 2.0, 3.0, 1.0)));
+    assert_eq!(doc.translater.translate::<Vector2<f32>>(&pon, &doc.bus), Ok(Vector2::new(1.0, 2.0)));
+}
+
+#[test]
+fn test_coerce_color() {
+    let mut translater = PonTranslater::new();
+    pon_std(&mut translater);
+    let doc = Document::new(translater);
+    let pon = Pon::from_string("'#ff00ff'").unwrap();
+    assert_eq!(doc.translater.translate::<Vector4<f32>>(&pon, &doc.bus), Ok(Vector4::new(1.0, 0.0, 1.0, 1.0)));
+}
+
+#[test]
+fn test_coerce_index() {
+    let mut translater = PonTranslater::new();
+    pon_std(&mut translater);
+    let doc = Document::new(translater);
+    assert_eq!(doc.translater.translate::<usize>(&Pon::Number(3.0), &doc.bus), Ok(3));
+    assert_eq!(doc.translater.translate::<usize>(&Pon::Integer(3), &doc.bus), Ok(3));
+    assert!(doc.translater.translate::<usize>(&Pon::Number(3.5), &doc.bus).is_err());
+    assert!(doc.translater.translate::<usize>(&Pon::Integer(-1), &doc.bus).is_err());
+}
+
+#[test]
+fn test_rectangle_to_pon() {
+    let mut translater = PonTranslater::new();
+    pon_std(&mut translater);
+    let doc = Document::new(translater);
+    let value: Box<BusValue> = Box::new(Rectangle { x: 1.0, y: 2.0, width: 3.0, height: 4.0 });
+    let pon = doc.translater.value_to_pon(&value);
+    assert_eq!(doc.translater.translate::<Rectangle>(&pon, &doc.bus), Ok(Rectangle { x: 1.0, y: 2.0, width: 3.0, height: 4.0 }));
+}
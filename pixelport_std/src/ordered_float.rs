@@ -2,8 +2,10 @@ use pixelport_document::*;
 
 use std::hash::Hasher;
 use std::hash::Hash;
+use std::cmp::Ordering;
 
-#[derive(Debug, Clone, PartialEq)]
+/// An f32 with a total order: NaN equals itself and sorts above everything else, and -0.0 equals 0.0.
+#[derive(Debug, Clone)]
 pub struct OrderedF32(f32);
 
 impl OrderedF32 {
@@ -15,12 +17,32 @@ impl OrderedF32 {
     }
 }
 
-impl Eq for OrderedF32 {
+impl PartialEq for OrderedF32 {
+    fn eq(&self, other: &OrderedF32) -> bool {
+        canonical_f32_bits(self.0) == canonical_f32_bits(other.0)
+    }
+}
+impl Eq for OrderedF32 {}
+
+impl PartialOrd for OrderedF32 {
+    fn partial_cmp(&self, other: &OrderedF32) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for OrderedF32 {
+    fn cmp(&self, other: &OrderedF32) -> Ordering {
+        match (self.0.is_nan(), other.0.is_nan()) {
+            (true, true) => Ordering::Equal,
+            (true, false) => Ordering::Greater,
+            (false, true) => Ordering::Less,
+            (false, false) => self.0.partial_cmp(&other.0).unwrap()
+        }
+    }
 }
 
 impl Hash for OrderedF32 {
-    fn hash<H>(&self, state: &mut H) where H: ::std::hash::Hasher {
-        format!("{}", self.0).hash(state);
+    fn hash<H>(&self, state: &mut H) where H: Hasher {
+        canonical_f32_bits(self.0).hash(state);
     }
 }
 